@@ -0,0 +1,233 @@
+// Syntax tree for a Jack class. Every node keeps the span of the source it
+// was parsed from so later passes can point at it in diagnostics.
+
+use span::Span;
+
+/// A name together with where it appears in the source
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Int,
+    Char,
+    Boolean,
+    Class(Ident),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Class {
+    pub name: Ident,
+    pub var_decs: Vec<ClassVarDec>,
+    pub subroutines: Vec<SubroutineDec>,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClassVarKind {
+    Static,
+    Field,
+}
+
+/// A static or field declaration, which can declare several variables
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassVarDec {
+    pub kind: ClassVarKind,
+    pub var_type: Type,
+    pub names: Vec<Ident>,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubroutineKind {
+    Constructor,
+    Function,
+    Method,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubroutineDec {
+    pub kind: SubroutineKind,
+    // None for void
+    pub return_type: Option<Type>,
+    pub name: Ident,
+    pub parameters: Vec<Parameter>,
+    pub locals: Vec<VarDec>,
+    pub body: Vec<Statement>,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+    pub var_type: Type,
+    pub name: Ident,
+}
+
+/// A var declaration at the start of a subroutine body
+#[derive(Clone, Debug, PartialEq)]
+pub struct VarDec {
+    pub var_type: Type,
+    pub names: Vec<Ident>,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+// The tree is built once per file, so statements are not boxed just to
+// make the variants the same size
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatementKind {
+    Let {
+        target: Ident,
+        // Set when assigning to an array element
+        index: Option<Expression>,
+        value: Expression,
+    },
+    If {
+        condition: Expression,
+        then_body: Vec<Statement>,
+        else_body: Option<Vec<Statement>>,
+    },
+    While {
+        condition: Expression,
+        body: Vec<Statement>,
+    },
+    Do(SubroutineCall),
+    Return(Option<Expression>),
+}
+
+/// Jack has no operator precedence, so an expression is a term followed by
+/// any number of operations that are applied from left to right
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    pub first: Term,
+    pub rest: Vec<(BinaryOp, Term)>,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    And,
+    Or,
+    Lt,
+    Gt,
+    Eq,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeywordConst {
+    True,
+    False,
+    Null,
+    This,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Term {
+    pub kind: TermKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TermKind {
+    IntConst(i32),
+    StringConst(String),
+    KeywordConst(KeywordConst),
+    Var(Ident),
+    // An array element, a[i]
+    Index(Ident, Box<Expression>),
+    Call(SubroutineCall),
+    Paren(Box<Expression>),
+    Unary(UnaryOp, Box<Term>),
+}
+
+/// A call like f(x), Foo.f(x) or foo.f(x)
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubroutineCall {
+    // The class or object before the dot, if any
+    pub receiver: Option<Ident>,
+    pub name: Ident,
+    pub args: Vec<Expression>,
+    pub span: Span,
+}
+
+impl Type {
+    /// The name of the type as it is written in the source
+    pub fn name(&self) -> &str {
+        match *self {
+            Type::Int => "int",
+            Type::Char => "char",
+            Type::Boolean => "boolean",
+            Type::Class(ref class) => &class.name,
+        }
+    }
+}
+
+impl SubroutineKind {
+    /// The keyword the subroutine is declared with
+    pub fn keyword(self) -> &'static str {
+        match self {
+            SubroutineKind::Constructor => "constructor",
+            SubroutineKind::Function => "function",
+            SubroutineKind::Method => "method",
+        }
+    }
+}
+
+impl BinaryOp {
+    pub fn from_symbol(sym: char) -> Option<BinaryOp> {
+        match sym {
+            '+' => Some(BinaryOp::Add),
+            '-' => Some(BinaryOp::Sub),
+            '*' => Some(BinaryOp::Mul),
+            '/' => Some(BinaryOp::Div),
+            '&' => Some(BinaryOp::And),
+            '|' => Some(BinaryOp::Or),
+            '<' => Some(BinaryOp::Lt),
+            '>' => Some(BinaryOp::Gt),
+            '=' => Some(BinaryOp::Eq),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(self) -> char {
+        match self {
+            BinaryOp::Add => '+',
+            BinaryOp::Sub => '-',
+            BinaryOp::Mul => '*',
+            BinaryOp::Div => '/',
+            BinaryOp::And => '&',
+            BinaryOp::Or => '|',
+            BinaryOp::Lt => '<',
+            BinaryOp::Gt => '>',
+            BinaryOp::Eq => '=',
+        }
+    }
+}
+
+impl UnaryOp {
+    pub fn symbol(self) -> char {
+        match self {
+            UnaryOp::Neg => '-',
+            UnaryOp::Not => '~',
+        }
+    }
+}
@@ -0,0 +1,200 @@
+// The classes of a whole project, collected from every file before any code
+// is generated so that each class can check how it uses the others
+
+use ast::*;
+use error::*;
+use os;
+use span::Span;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// A static or field of a class
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariableInfo {
+    pub name: String,
+    pub type_name: String,
+    pub span: Option<Span>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParameterInfo {
+    pub name: String,
+    pub type_name: String,
+}
+
+/// What the callers of a subroutine need to know about it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signature {
+    pub kind: SubroutineKind,
+    pub name: String,
+    // None for void subroutines
+    pub return_type: Option<String>,
+    pub parameters: Vec<ParameterInfo>,
+    // Where the subroutine is declared, if it is in the project
+    pub span: Option<Span>,
+}
+
+impl Signature {
+    pub fn from_subroutine(subroutine: &SubroutineDec) -> Signature {
+        Signature {
+            kind: subroutine.kind,
+            name: subroutine.name.name.clone(),
+            return_type: subroutine.return_type.as_ref().map(|t| t.name().to_string()),
+            parameters: subroutine.parameters.iter()
+                .map(|parameter| ParameterInfo {
+                    name: parameter.name.name.clone(),
+                    type_name: parameter.var_type.name().to_string(),
+                })
+                .collect(),
+            span: Some(subroutine.name.span.clone()),
+        }
+    }
+}
+
+/// Shows the signature as it is declared, like `function int max(int a, int b)`
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parameters: Vec<String> = self.parameters.iter()
+            .map(|parameter| format!("{} {}", parameter.type_name, parameter.name))
+            .collect();
+        write!(f, "{} {} {}({})", self.kind.keyword(), self.return_type.as_ref().map_or("void", |t| t),
+               self.name, parameters.join(", "))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassInfo {
+    pub name: String,
+    pub statics: Vec<VariableInfo>,
+    pub fields: Vec<VariableInfo>,
+    pub subroutines: Vec<Signature>,
+    // Where the class is declared, if it is in the project
+    pub span: Option<Span>,
+}
+
+impl ClassInfo {
+    pub fn subroutine(&self, name: &str) -> Option<&Signature> {
+        self.subroutines.iter().find(|subroutine| subroutine.name == name)
+    }
+}
+
+/// Every class of a project by name
+#[derive(Clone, Debug, Default)]
+pub struct ClassIndex {
+    classes: HashMap<String, ClassInfo>,
+    // Whether the index holds every class of the program, so that other
+    // classes do not exist
+    complete: bool,
+}
+
+impl ClassIndex {
+    pub fn new() -> ClassIndex {
+        ClassIndex::default()
+    }
+
+    /// An index that starts with the classes of the Jack OS
+    pub fn with_os() -> ClassIndex {
+        let mut index = ClassIndex::new();
+        for class in os::classes() {
+            index.insert(class);
+        }
+        index
+    }
+
+    /// Add the declarations of a class. A class with the name of one that
+    /// is already in the project is not added, and subroutines defined twice
+    /// are only added once, with an error for each. Classes from outside
+    /// the project, like those of the OS, are replaced so that a project
+    /// can supply its own OS.
+    pub fn add_class(&mut self, class: &Class) -> Result<(), Vec<CompileError>> {
+        let name = &class.name;
+        let previous = self.classes.get(&name.name).filter(|previous| previous.span.is_some());
+        if let Some(previous) = previous {
+            return Err(vec![
+                CompileError::semantic(name.span.clone(), format!("Class {} is already defined", name.name))
+                    .with_code(E_DUPLICATE_DEFINITION)
+                    .with_note(previous.span.clone(), format!("previous definition of {} is here", name.name))
+            ]);
+        }
+
+        let mut errors = Vec::new();
+        let mut info = ClassInfo {
+            name: name.name.clone(),
+            statics: Vec::new(),
+            fields: Vec::new(),
+            subroutines: Vec::new(),
+            span: Some(name.span.clone()),
+        };
+
+        // Statics and fields defined twice are reported by the symbol table
+        for var_dec in &class.var_decs {
+            let variables = match var_dec.kind {
+                ClassVarKind::Static => &mut info.statics,
+                ClassVarKind::Field => &mut info.fields,
+            };
+            for var_name in &var_dec.names {
+                variables.push(VariableInfo {
+                    name: var_name.name.clone(),
+                    type_name: var_dec.var_type.name().to_string(),
+                    span: Some(var_name.span.clone()),
+                });
+            }
+        }
+
+        for subroutine in &class.subroutines {
+            let subroutine_name = &subroutine.name;
+            if let Some(previous) = info.subroutine(&subroutine_name.name) {
+                errors.push(
+                    CompileError::semantic(subroutine_name.span.clone(), format!(
+                        "Subroutine {} is already defined in class {}", subroutine_name.name, name.name))
+                        .with_code(E_DUPLICATE_DEFINITION)
+                        .with_note(previous.span.clone(),
+                                   format!("previous definition of {} is here", subroutine_name.name)));
+                continue;
+            }
+            info.subroutines.push(Signature::from_subroutine(subroutine));
+        }
+
+        self.classes.insert(info.name.clone(), info);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Add a class that is not in the project, replacing any class with
+    /// the same name
+    pub fn insert(&mut self, class: ClassInfo) {
+        self.classes.insert(class.name.clone(), class);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ClassInfo> {
+        self.classes.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.classes.contains_key(name)
+    }
+
+    /// The signature of a subroutine of a class
+    pub fn subroutine(&self, class_name: &str, name: &str) -> Option<&Signature> {
+        self.get(class_name).and_then(|class| class.subroutine(name))
+    }
+
+    /// Mark the index as holding every class of the program. Otherwise
+    /// classes that are not in it are assumed to be compiled separately.
+    pub fn set_complete(&mut self, complete: bool) {
+        self.complete = complete;
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Every class in the index, in no particular order
+    pub fn classes(&self) -> impl Iterator<Item = &ClassInfo> {
+        self.classes.values()
+    }
+}
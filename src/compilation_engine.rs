@@ -1,17 +1,35 @@
-use jack_analyzer::*;
+use ast::*;
+use class_index::{ClassIndex, Signature};
+use error::*;
+use jack_analyzer::hack_char_code;
+use span::Span;
+use suggest;
 use vm_writer::*;
 use symbol_table::*;
-use xml_output::keyword_to_str;
-use xml_output::make_tag_string;
+use vfs::OutputSink;
 
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
 use std::path::Path;
+use std::rc::Rc;
 
-pub struct CompilationEngine {
-    analyzer: JackAnalyzer,
-    vm_writer: VMWriter,
+/// Generates VM code by walking the syntax tree of a class
+pub struct CompilationEngine<W: Write> {
+    vm_writer: VMWriter<W>,
+    outfile: String,
     symbol_table: SymbolTable,
     class_name: String,
+    // The full name of the subroutine being compiled and its kind, which
+    // tells if there is a this to call methods on
+    subroutine_name: String,
+    subroutine_kind: SubroutineKind,
+    // None for void subroutines
+    return_type: Option<String>,
+    // The classes of the project and the OS, for checking the uses of them
+    class_index: Option<Rc<ClassIndex>>,
     label_num: i32,
+    errors: Vec<CompileError>,
 }
 
 fn kind_to_segment(kind: Kind) -> Segment {
@@ -24,571 +42,640 @@ fn kind_to_segment(kind: Kind) -> Segment {
     }
 }
 
-fn symbol_to_command(sym: char) -> Command {
-    match sym {
-        '+' => Command::Add,
-        '-' => Command::Sub,
-        '&' => Command::And,
-        '|' => Command::Or,
-        '<' => Command::Lt,
-        '>' => Command::Gt,
-        '=' => Command::Eq,
-        _ => panic!(format!("symbol {} does not have an associated command", sym))
+fn op_to_command(op: BinaryOp) -> Command {
+    match op {
+        BinaryOp::Add => Command::Add,
+        BinaryOp::Sub => Command::Sub,
+        BinaryOp::And => Command::And,
+        BinaryOp::Or => Command::Or,
+        BinaryOp::Lt => Command::Lt,
+        BinaryOp::Gt => Command::Gt,
+        BinaryOp::Eq => Command::Eq,
+        _ => panic!("operator {} does not have an associated command", op.symbol())
     }
 }
 
-// TODO: maybe make everything more rusty with results instead of panics
-// TODO: have some way of reporting line number on errors (maybe count lines in JackAnalyzer)
+// Whether a block of statements can only be left by returning. Loops might
+// not run at all, unless they loop forever.
+fn always_returns(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match statement.kind {
+        StatementKind::Return(_) => true,
+        StatementKind::If { ref then_body, else_body: Some(ref else_body), .. } =>
+            always_returns(then_body) && always_returns(else_body),
+        StatementKind::While { ref condition, .. } =>
+            condition.rest.is_empty() && condition.first.kind == TermKind::KeywordConst(KeywordConst::True),
+        _ => false,
+    })
+}
+
+// Check that a call passes as many arguments as the subroutine has
+// parameters. The object a method is called on is not counted.
+fn check_argument_count(call: &SubroutineCall, class_name: &str, signature: &Signature) -> CompileResult<()> {
+    let expected = signature.parameters.len();
+    let given = call.args.len();
+    if given == expected {
+        return Ok(());
+    }
+    let arguments = if expected == 1 { "argument" } else { "arguments" };
+    let were = if given == 1 { "was" } else { "were" };
+    Err(CompileError::semantic(call.span.clone(), format!(
+        "{}.{} takes {} {} but {} {} given", class_name, signature.name, expected, arguments, given, were))
+        .with_code(E_ARGUMENT_COUNT)
+        .with_note(signature.span.clone(), format!("{}.{} is declared as {}", class_name, signature.name, signature)))
+}
+
+impl CompilationEngine<BufWriter<File>> {
+    pub fn new(outfile: &Path) -> CompileResult<CompilationEngine<BufWriter<File>>> {
+        let outfile = outfile.display().to_string();
+        match VMWriter::new(Path::new(&outfile)) {
+            Ok(writer) => Ok(CompilationEngine::from_vm_writer(&outfile, writer)),
+            Err(why) => Err(CompileError::io(&outfile, &why)),
+        }
+    }
+}
 
-impl CompilationEngine {
-    pub fn new(infile: &Path, outfile: &Path) -> CompilationEngine {
+impl CompilationEngine<Box<dyn Write>> {
+    /// Write the VM code to a file created in an output sink
+    pub fn create(sink: &mut dyn OutputSink, outfile: &Path) -> CompileResult<CompilationEngine<Box<dyn Write>>> {
+        let outfile = outfile.display().to_string();
+        match VMWriter::create(sink, Path::new(&outfile)) {
+            Ok(writer) => Ok(CompilationEngine::from_vm_writer(&outfile, writer)),
+            Err(why) => Err(CompileError::io(&outfile, &why)),
+        }
+    }
+}
+
+impl<W: Write> CompilationEngine<W> {
+    /// Write the VM code to any writer. `outfile` is the name used in
+    /// errors when writing fails.
+    pub fn from_writer(outfile: &str, out: W) -> CompilationEngine<W> {
+        CompilationEngine::from_vm_writer(outfile, VMWriter::from_writer(out))
+    }
+
+    fn from_vm_writer(outfile: &str, vm_writer: VMWriter<W>) -> CompilationEngine<W> {
         CompilationEngine {
-            analyzer: JackAnalyzer::new(infile),
-            vm_writer: VMWriter::new(outfile),
+            vm_writer,
+            outfile: outfile.to_string(),
             symbol_table: SymbolTable::new(),
             class_name: String::new(),
+            subroutine_name: String::new(),
+            subroutine_kind: SubroutineKind::Function,
+            return_type: None,
+            class_index: None,
             label_num: 0,
+            errors: Vec::new(),
         }
     }
-    
+
+    /// Check calls and types against the classes of a project. Without an
+    /// index other classes are assumed to exist and to be used correctly.
+    pub fn with_class_index(mut self, class_index: Rc<ClassIndex>) -> CompilationEngine<W> {
+        self.class_index = Some(class_index);
+        self
+    }
+
+    /// Get back the writer the VM code was written to
+    pub fn into_inner(self) -> W {
+        self.vm_writer.into_inner()
+    }
+
     fn gen_label_num(&mut self) -> String {
         self.label_num += 1;
         self.label_num.to_string()
     }
 
-    pub fn compile_class(&mut self) {
-        self.analyzer.advance();
-        if self.analyzer.token_type() != TokenType::Keyword || self.analyzer.key_word().unwrap() != Keyword::Class {
-            panic!("File must start with class");
-        }
-        
-        self.analyzer.advance();
-        if self.analyzer.token_type() != TokenType::Identifier {
-            panic!("No class name");
+    // Find the segment and index of a variable. Fields are in scope in
+    // functions too, but there is no this to find them in.
+    fn variable(&self, name: &Ident) -> CompileResult<(Segment, i32)> {
+        match self.symbol_table.get(&name.name) {
+            Some(symbol) if symbol.kind == Kind::Field && self.subroutine_kind == SubroutineKind::Function => {
+                Err(CompileError::semantic(name.span.clone(), format!(
+                    "Cannot use field {} in function {}", name.name, self.subroutine_name))
+                    .with_code(E_THIS_IN_FUNCTION)
+                    .with_note(symbol.span.clone(), format!("{} is declared here", name.name))
+                    .with_help("functions have no this to read fields from, so make it a method or pass the \
+                                object as an argument"))
+            }
+            Some(symbol) => Ok((kind_to_segment(symbol.kind), symbol.index)),
+            None => {
+                let error = CompileError::semantic(name.span.clone(), format!("Undefined variable {}", name.name))
+                    .with_code(E_UNDEFINED_VARIABLE);
+                Err(match suggest::closest(&name.name, self.symbol_table.names()) {
+                    Some(candidate) => error.with_help(format!("did you mean `{}`?", candidate)),
+                    None => error.with_help("variables must be declared with var, static or field before they \
+                                             are used"),
+                })
+            }
         }
-        self.class_name = self.analyzer.identifier();
+    }
 
-        self.analyzer.advance();
-        if self.analyzer.token_type() != TokenType::Symbol || self.analyzer.symbol() != '{' {
-            panic!("Missing opening brace");
+    // Report a declaration with a class type that is not in the index, if
+    // the index has every class of the program
+    fn check_type(&mut self, var_type: &Type) {
+        let class_index = match self.class_index {
+            Some(ref class_index) if class_index.is_complete() => class_index,
+            _ => return,
+        };
+        if let Type::Class(ref class) = *var_type {
+            if !class_index.contains(&class.name) {
+                let error = CompileError::semantic(class.span.clone(), format!("Undefined class {}", class.name))
+                    .with_code(E_UNDEFINED_CLASS);
+                let classes = class_index.classes().map(|class| &*class.name);
+                self.errors.push(suggest::with_suggestion(error, &class.name, classes));
+            }
         }
+    }
 
-        let is_class_var = |keyword: Keyword| keyword == Keyword::Static ||
-            keyword == Keyword::Field;
-
-        let is_subroutine = |keyword: Keyword| match keyword {
-            Keyword::Constructor | Keyword::Function | Keyword::Method => true,
-            _ => false
+    // Find the subroutine a call is to in the index. Calls to classes that
+    // are not in it give None, unless the index has every class of the
+    // program. Even then calls on variables of unknown classes give None,
+    // since the declaration is reported.
+    fn find_subroutine<'a>(&self, class_index: &'a ClassIndex, call: &SubroutineCall, class_name: &str,
+                           on_object: bool) -> CompileResult<Option<&'a Signature>> {
+        let class = match class_index.get(class_name) {
+            Some(class) => class,
+            None => match call.receiver {
+                Some(ref receiver) if !on_object && class_index.is_complete() => {
+                    let error = CompileError::semantic(receiver.span.clone(), format!(
+                        "Undefined class or variable {}", receiver.name))
+                        .with_code(E_UNDEFINED_CLASS);
+                    let names = class_index.classes().map(|class| &*class.name).chain(self.symbol_table.names());
+                    return Err(suggest::with_suggestion(error, &receiver.name, names));
+                }
+                _ => return Ok(None),
+            },
         };
 
-        self.analyzer.advance();
-        while !(self.analyzer.token_type() == TokenType::Symbol && self.analyzer.symbol() == '}') {
-            match self.analyzer.token_type() {
-                TokenType::Keyword if is_class_var(self.analyzer.key_word().unwrap()) => self.compile_class_var_dec(),
-                TokenType::Keyword if is_subroutine(self.analyzer.key_word().unwrap()) => self.compile_subroutine(),
-                _ => panic!("Unknown token inside class: "),
-            };
-        }
-    }
-    
-    fn compile_generic_var_dec(&mut self) {
-        // Check if it's a static variable, field, or local variable
-        let kind = keyword_to_kind(self.analyzer.key_word().unwrap());
-        self.analyzer.advance();
-        
-        // Get the type of the variable
-        let type_name = self.analyzer.identifier();
-        self.analyzer.advance();
-        
-        // Define all variables
-        while !(self.analyzer.token_type() == TokenType::Symbol && self.analyzer.symbol() == ';') {
-            // Skip commas
-            if self.analyzer.token_type() == TokenType::Symbol && self.analyzer.symbol() == ',' {
-                self.analyzer.advance();
+        match class.subroutine(&call.name.name) {
+            Some(signature) => Ok(Some(signature)),
+            None => {
+                let error = CompileError::semantic(call.name.span.clone(), format!(
+                    "Class {} has no subroutine named {}", class_name, call.name.name))
+                    .with_code(E_UNDEFINED_SUBROUTINE)
+                    .with_note(class.span.clone(), format!("{} is declared here", class_name));
+                let subroutines = class.subroutines.iter().map(|subroutine| &*subroutine.name);
+                Err(suggest::with_suggestion(error, &call.name.name, subroutines))
             }
-            // Get the name of the variable
-            let name = self.analyzer.identifier();
-            self.symbol_table.define(&name, &type_name, kind);
-            self.analyzer.advance();
         }
     }
 
-    pub fn compile_class_var_dec(&mut self) {
-        self.compile_generic_var_dec();
-        // Skip semicolon
-        self.analyzer.advance();
+    // Add a variable to the symbol table, reporting it if the name is taken
+    fn define(&mut self, var_type: &Type, name: &Ident, kind: Kind) {
+        let defined = self.symbol_table.define(&name.name, var_type.name(), kind,
+                                               Some(name.span.clone()));
+        if let Err(previous) = defined {
+            self.errors.push(
+                CompileError::semantic(name.span.clone(), format!("Variable {} is already defined", name.name))
+                    .with_code(E_DUPLICATE_DEFINITION)
+                    .with_note(previous.span, format!("previous definition of {} is here", name.name)));
+        }
     }
 
-    pub fn compile_subroutine(&mut self) {
-        // Clear symbol table
-        self.symbol_table.start_subroutine();
+    // Call a subroutine of the OS that `construct` is compiled to. An OS
+    // supplied with the project must have it too.
+    fn write_os_call(&mut self, span: &Span, construct: &str, class_name: &str, name: &str, n_args: i32)
+                     -> CompileResult<()> {
+        if let Some(class) = self.class_index.as_ref().and_then(|class_index| class_index.get(class_name)) {
+            if class.subroutine(name).is_none() {
+                return Err(CompileError::semantic(span.clone(), format!(
+                    "Class {} has no subroutine named {}", class_name, name))
+                           .with_code(E_UNDEFINED_SUBROUTINE)
+                           .with_note(class.span.clone(), format!("{} is declared here", class_name))
+                           .with_help(format!("{} is compiled to a call to {}.{}", construct, class_name, name)));
+            }
+        }
+        self.vm_writer.write_call(&format!("{}.{}", class_name, name), n_args);
+        Ok(())
+    }
 
-        let subroutine_type = self.analyzer.key_word().unwrap();
-        self.analyzer.advance();
+    /// Compile the whole class, returning every error that was found
+    pub fn compile_class(&mut self, class: &Class) -> Result<(), Vec<CompileError>> {
+        self.class_name = class.name.name.clone();
 
-        if subroutine_type == Keyword::Method {
-            // Make room for this pointer in parameters
-            self.symbol_table.define(&String::new(), &String::new(), Kind::Arg);
+        for var_dec in &class.var_decs {
+            self.compile_class_var_dec(var_dec);
         }
-
-        if !(self.analyzer.token_type() == TokenType::Identifier || self.analyzer.token_type() == TokenType::Keyword) {
-            panic!("No return type");
+        for subroutine in &class.subroutines {
+            self.compile_subroutine(subroutine);
         }
-        self.analyzer.advance();
 
-        if self.analyzer.token_type() != TokenType::Identifier {
-            panic!("No function name");
+        if let Err(why) = self.vm_writer.finish() {
+            self.errors.push(CompileError::io(&self.outfile, &why));
         }
-        let fn_name = format!("{}.{}", self.class_name, self.analyzer.identifier());
-        self.analyzer.advance();
 
-        if self.analyzer.token_type() != TokenType::Symbol || self.analyzer.symbol() != '(' {
-            panic!("Missing parameter list");
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors.drain(..).collect())
         }
-        self.analyzer.advance();
+    }
 
-        self.compile_parameter_list();
-        if self.analyzer.token_type() != TokenType::Symbol || self.analyzer.symbol() != ')' {
-            panic!("Missing closing parenthesis");
+    pub fn compile_class_var_dec(&mut self, var_dec: &ClassVarDec) {
+        self.check_type(&var_dec.var_type);
+        for name in &var_dec.names {
+            self.define(&var_dec.var_type, name, class_var_kind(var_dec.kind));
         }
+    }
 
-        self.analyzer.advance();
-        if self.analyzer.token_type() != TokenType::Symbol || self.analyzer.symbol() != '{' {
-            panic!("Missing function opening brace");
+    pub fn compile_subroutine(&mut self, subroutine: &SubroutineDec) {
+        // Clear symbol table
+        self.symbol_table.start_subroutine();
+        self.subroutine_name = format!("{}.{}", self.class_name, subroutine.name.name);
+        self.subroutine_kind = subroutine.kind;
+        self.return_type = subroutine.return_type.as_ref().map(|t| t.name().to_string());
+
+        if subroutine.kind == SubroutineKind::Method {
+            // Make room for this pointer in parameters. The name is a keyword
+            // so it can't clash with a parameter.
+            let this_type = self.class_name.clone();
+            let _ = self.symbol_table.define("this", &this_type, Kind::Arg, None);
+        }
+        if let Some(ref return_type) = subroutine.return_type {
+            self.check_type(return_type);
+        }
+        for parameter in &subroutine.parameters {
+            self.check_type(&parameter.var_type);
+            self.define(&parameter.var_type, &parameter.name, Kind::Arg);
+        }
+        for var_dec in &subroutine.locals {
+            self.check_type(&var_dec.var_type);
+            for name in &var_dec.names {
+                self.define(&var_dec.var_type, name, Kind::Var);
+            }
         }
 
-        // Parse local variable declarations
-        self.analyzer.advance();
-        while self.analyzer.token_type() == TokenType::Keyword && self.analyzer.key_word().unwrap() == Keyword::Var {
-            self.compile_var_dec();
-        }
-        
         let n_local = self.symbol_table.var_count(Kind::Var);
-        self.vm_writer.write_function(&fn_name, n_local);
+        self.vm_writer.write_function(&self.subroutine_name, n_local);
 
         // Set up this pointer
-        if subroutine_type == Keyword::Constructor {
+        if subroutine.kind == SubroutineKind::Constructor {
             // Allocate space for the object
             self.vm_writer.write_push(Segment::Const, self.symbol_table.var_count(Kind::Field));
             self.vm_writer.write_call("Memory.alloc", 1);
             self.vm_writer.write_pop(Segment::Pointer, 0);
-        } else if subroutine_type == Keyword::Method {
+        } else if subroutine.kind == SubroutineKind::Method {
             // Set this pointer to current object
             self.vm_writer.write_push(Segment::Arg, 0);
             self.vm_writer.write_pop(Segment::Pointer, 0);
         }
 
         // Write main body of subroutine
-        self.compile_statements();
+        self.compile_statements(&subroutine.body);
 
-        // Skip closing brace
-        self.analyzer.advance();
+        // The VM would run past the end of the function
+        if !always_returns(&subroutine.body) {
+            let help = match self.return_type {
+                Some(_) => "add a return statement with a value at the end",
+                None => "void subroutines need a return; at the end too",
+            };
+            self.errors.push(
+                CompileError::semantic(subroutine.name.span.clone(), format!(
+                    "{} can reach its end without returning", self.subroutine_name))
+                    .with_code(E_MISSING_RETURN)
+                    .with_help(help));
+        }
     }
 
-    pub fn compile_parameter_list(&mut self) {
-        while !(self.analyzer.token_type() == TokenType::Symbol && self.analyzer.symbol() == ')') {
-            // Skip commas between arguments
-            if self.analyzer.token_type() == TokenType::Symbol && self.analyzer.symbol() == ',' {
-                self.analyzer.advance();
+    pub fn compile_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            let result = match statement.kind {
+                StatementKind::Let { ref target, ref index, ref value } =>
+                    self.compile_let(target, index.as_ref(), value),
+                StatementKind::If { ref condition, ref then_body, ref else_body } =>
+                    self.compile_if(condition, then_body, else_body.as_ref()),
+                StatementKind::While { ref condition, ref body } => self.compile_while(condition, body),
+                StatementKind::Do(ref call) => self.compile_do(call),
+                StatementKind::Return(ref value) => self.compile_return(&statement.span, value.as_ref()),
+            };
+
+            // Report the error and continue with the next statement
+            if let Err(error) = result {
+                self.errors.push(error);
             }
-            // Get the type of the variable
-            let type_name = self.analyzer.identifier();
-            self.analyzer.advance();
-
-            // Get the name of the variable
-            let name = self.analyzer.identifier();
-            self.symbol_table.define(&name, &type_name, Kind::Arg);
-            self.analyzer.advance();
         }
     }
 
-    pub fn compile_var_dec(&mut self) {
-        self.compile_generic_var_dec();
-        // Skip semicolon
-        self.analyzer.advance();
-    }
-
-    pub fn compile_statements(&mut self) {
-        while !(self.analyzer.token_type() == TokenType::Symbol && self.analyzer.symbol() == '}') {
-            if self.analyzer.token_type() != TokenType::Keyword {
-                panic!("Statement must begin with keyword");
+    fn compile_subroutine_call(&mut self, call: &SubroutineCall) -> CompileResult<()> {
+        // Find the class of the subroutine, and the object it is called on
+        // if it is a method
+        let (class_name, object) = match call.receiver {
+            Some(ref receiver) => {
+                // receiver is a the name of a class or an object
+                let kind = self.symbol_table.kind_of(&receiver.name);
+                // If it's a static function we can just write the class name, otherwise we need to find it
+                if kind == Kind::None {
+                    (receiver.name.clone(), None)
+                } else {
+                    let type_name = self.symbol_table.type_of(&receiver.name).unwrap();
+                    if type_name == "int" || type_name == "char" || type_name == "boolean" {
+                        return Err(CompileError::semantic(receiver.span.clone(), format!(
+                            "Cannot call {} on {} since it has type {}", call.name.name, receiver.name, type_name))
+                                   .with_code(E_NOT_AN_OBJECT)
+                                   .with_note(self.symbol_table.span_of(&receiver.name),
+                                              format!("{} is declared here", receiver.name)));
+                    }
+                    (type_name, Some(self.variable(receiver)?))
+                }
             }
+            // Subroutine of this class, called on this if it is a method
+            None => (self.class_name.clone(), Some((Segment::Pointer, 0))),
+        };
 
-            match self.analyzer.key_word().unwrap() {
-                Keyword::Let => self.compile_let(),
-                Keyword::If => self.compile_if(),
-                Keyword::While => self.compile_while(),
-                Keyword::Do => self.compile_do(),
-                Keyword::Return => self.compile_return(),
-                other => panic!("Invalid keyword at start of statement: ".to_string() +
-                           keyword_to_str(&other)),
-            };
+        // Classes in the project and the OS must have the subroutine
+        let class_index = self.class_index.clone();
+        let signature = match class_index {
+            Some(ref class_index) => {
+                let on_object = call.receiver.is_none() || object.is_some();
+                self.find_subroutine(class_index, call, &class_name, on_object)?
+            }
+            None => None,
+        };
+        let object = self.check_call_kind(call, &class_name, object, signature)?;
+        if let Some(signature) = signature {
+            check_argument_count(call, &class_name, signature)?;
         }
-    }
-    
-    fn compile_function_call(&mut self, sym: char, name1: String) {
-        //println!("at start of function call. state = {}, sym = {}", make_tag_string(&self.analyzer), sym.to_string());
+
+        // Push the object to the stack
         let mut n_args = 0;
-        let full_name = if sym == '.' {
-            // name1 is a the name of a class or an object
-            let f_name = self.analyzer.identifier();
-            self.analyzer.advance();
-            let kind = self.symbol_table.kind_of(&name1);
-            // If it's a static function we can just write the class name, otherwise we need to find it
-            let class_name = if kind == Kind::None {
-                name1
-            } else {
-                // Push the object to the stack
-                self.vm_writer.write_push(kind_to_segment(kind), self.symbol_table.index_of(&name1));
-                n_args += 1;
-                self.symbol_table.type_of(&name1)
-            };
-        
-            if self.analyzer.token_type() != TokenType::Symbol || self.analyzer.symbol() != '(' {
-                panic!(format!("Expected ( after function name. found {} instead", make_tag_string(&self.analyzer)));
-            }
-            self.analyzer.advance();
-            
-            class_name + "." + &f_name
-        } else if sym == '(' {
-            // name1 is the function name
-            // Local function, push this to stack
-            self.vm_writer.write_push(Segment::Pointer, 0);
+        if let Some((seg, index)) = object {
+            self.vm_writer.write_push(seg, index);
             n_args += 1;
-            format!("{}.{}", self.class_name, name1)
-        } else {
-            panic!("Expected on of . and ( after identifier in function call")
-        };
+        }
 
         // Push parameters
-        n_args += self.compile_expression_list();
-
-        // Skip )
-        if self.analyzer.token_type() != TokenType::Symbol || self.analyzer.symbol() != ')' {
-            panic!(format!("Expected ) after function name. found {} instead", make_tag_string(&self.analyzer)));
-        }
-        self.analyzer.advance();
+        n_args += self.compile_expression_list(&call.args)?;
 
-        self.vm_writer.write_call(&full_name, n_args);
+        self.vm_writer.write_call(&format!("{}.{}", class_name, call.name.name), n_args);
+        Ok(())
     }
 
-    pub fn compile_do(&mut self) {
-        // Skip do
-        self.analyzer.advance();
-
-        let name1 = self.analyzer.identifier();
-        self.analyzer.advance();
-
-        if self.analyzer.token_type() != TokenType::Symbol {
-            panic!("Symbol . or ( expected after identifier in do statement")
-        }
-        let sym = self.analyzer.symbol();
-        self.analyzer.advance();
-
-        // Check what kind of function it is, call it and push return value to stack
-        self.compile_function_call(sym, name1);
+    // Check that a subroutine is called in the way its kind needs: methods
+    // on an object and functions and constructors on their class. Returns
+    // the object to push for the call. Subroutines of classes that are not
+    // known are assumed to be called correctly.
+    fn check_call_kind(&self, call: &SubroutineCall, class_name: &str, object: Option<(Segment, i32)>,
+                       signature: Option<&Signature>) -> CompileResult<Option<(Segment, i32)>> {
+        let has_this = self.subroutine_kind != SubroutineKind::Function;
+        let kind = match signature {
+            Some(signature) => signature.kind,
+            // Without a signature an unqualified call is taken to be a method
+            // call if there is a this to call it on
+            None if call.receiver.is_none() && !has_this => return Ok(None),
+            None => return Ok(object),
+        };
+        let is_method = kind == SubroutineKind::Method;
+        let name = format!("{}.{}", class_name, call.name.name);
+
+        let error = match (call.receiver.as_ref(), object) {
+            // f() is called on this if it is a method
+            (None, object) => {
+                if !is_method {
+                    return Ok(None);
+                } else if has_this {
+                    return Ok(object);
+                }
+                CompileError::semantic(call.span.clone(), format!(
+                    "Cannot call method {} from function {}", call.name.name, self.subroutine_name))
+                    .with_help("functions have no this to call methods on, so make it a method or call the \
+                                method on an object")
+            }
+            // Foo.f() is a function or constructor
+            (Some(receiver), None) => {
+                if !is_method {
+                    return Ok(None);
+                }
+                CompileError::semantic(call.span.clone(), format!(
+                    "Cannot call method {} on the class {}", name, receiver.name))
+                    .with_help(format!("methods are called on an object, like x.{}()", call.name.name))
+            }
+            // foo.f() is a method
+            (Some(receiver), Some(object)) => {
+                if is_method {
+                    return Ok(Some(object));
+                }
+                CompileError::semantic(call.span.clone(), format!(
+                    "Cannot call {} {} on the object {}", kind.keyword(), name, receiver.name))
+                    .with_help(format!("{}s are called on their class, like {}()", kind.keyword(), name))
+            }
+        };
 
-        if self.analyzer.token_type() != TokenType::Symbol || self.analyzer.symbol() != ';' {
-            panic!("Expected ; at end of do statement");
-        }
-        self.analyzer.advance();
+        let error = error.with_code(E_INVALID_CALL);
+        Err(match signature.and_then(|signature| signature.span.clone()) {
+            Some(span) => error.with_note(Some(span), format!("{} is declared here", name)),
+            None => error,
+        })
+    }
 
-        // Ignore return value
+    pub fn compile_do(&mut self, call: &SubroutineCall) -> CompileResult<()> {
+        // Call the function and ignore the return value
+        self.compile_subroutine_call(call)?;
         self.vm_writer.write_pop(Segment::Temp, 0);
+        Ok(())
     }
 
-    pub fn compile_let(&mut self) {
-        // Skip let keyword
-        self.analyzer.advance();
-
-        // Parse variable name
-        let var_name = self.analyzer.identifier();
-        self.analyzer.advance();
-        
-        let kind = self.symbol_table.kind_of(&var_name);
-        let seg = kind_to_segment(kind);
-        let index = self.symbol_table.index_of(&var_name);
+    pub fn compile_let(&mut self, target: &Ident, index: Option<&Expression>, value: &Expression) -> CompileResult<()> {
+        let (seg, var_index) = match self.variable(target) {
+            Ok(variable) => variable,
+            Err(error) => {
+                // Report the errors in the index and value too
+                self.errors.push(error);
+                if let Some(index) = index {
+                    if let Err(error) = self.compile_expression(index) {
+                        self.errors.push(error);
+                    }
+                }
+                return self.compile_expression(value);
+            }
+        };
 
         // handle array element assignment
-        if self.analyzer.token_type() == TokenType::Symbol && self.analyzer.symbol() == '[' {
-            // Skip [
-            self.analyzer.advance();
-
+        if let Some(index) = index {
             // Place index on stack
-            self.compile_expression();
-            
-            // Skip ] TODO: check
-            self.analyzer.advance();
-        
-            // Skip = TODO: check
-            self.analyzer.advance();
+            self.compile_expression(index)?;
 
             // Calculate address
-            self.vm_writer.write_push(seg, index);
+            self.vm_writer.write_push(seg, var_index);
             self.vm_writer.write_arithmetic(Command::Add);
 
             // Place expression result on stack and do the assignment
             // temp stuff is required since compile_expression might change pointer 1
-            self.compile_expression();
+            self.compile_expression(value)?;
             self.vm_writer.write_pop(Segment::Temp, 0);
             self.vm_writer.write_pop(Segment::Pointer, 1);
             self.vm_writer.write_push(Segment::Temp, 0);
             self.vm_writer.write_pop(Segment::That, 0)
         } else {
-            // Skip = TODO: check
-            self.analyzer.advance();
-
-            self.compile_expression();
-
-            self.vm_writer.write_pop(seg, index);
+            self.compile_expression(value)?;
+            self.vm_writer.write_pop(seg, var_index);
         }
-
-        // Skip semicolon
-        self.analyzer.advance();
+        Ok(())
     }
 
-    pub fn compile_while(&mut self) {
-        // Skip while keyword
-        self.analyzer.advance();
-        if self.analyzer.token_type() != TokenType::Symbol || self.analyzer.symbol() != '(' {
-            panic!("Missing expression for while loop");
-        }
-        // Skip (
-        self.analyzer.advance();
-
+    pub fn compile_while(&mut self, condition: &Expression, body: &[Statement]) -> CompileResult<()> {
         // TODO: add variable for num
         let while_label = "while".to_string() + &self.gen_label_num();
         let end_label = &format!("{}end", while_label);
         self.vm_writer.write_label(&while_label);
-        // Calculate expression and check if loop should be continued
-        self.compile_expression();
-        self.vm_writer.write_arithmetic(Command::Not);
-        self.vm_writer.write_if(&end_label);
-
-        if self.analyzer.token_type() != TokenType::Symbol || self.analyzer.symbol() != ')' {
-            panic!("Missing closing parenthesis for while expression");
-        }
-        // Skip )
-        self.analyzer.advance();
-
-        if self.analyzer.token_type() != TokenType::Symbol || self.analyzer.symbol() != '{' {
-            panic!("Missing opening brace on while loop");
+        // Calculate expression and check if loop should be continued. The
+        // body is still compiled after an error, to report the errors in it.
+        if let Err(error) = self.compile_expression(condition) {
+            self.errors.push(error);
         }
-        // Skip {
-        self.analyzer.advance();
+        self.vm_writer.write_arithmetic(Command::Not);
+        self.vm_writer.write_if(end_label);
 
         // Compile statements inside loop
-        self.compile_statements();
-
-        // Skip } TODO: check
-        self.analyzer.advance();
+        self.compile_statements(body);
 
         self.vm_writer.write_goto(&while_label);
-        self.vm_writer.write_label(&end_label)
+        self.vm_writer.write_label(end_label);
+        Ok(())
     }
 
-    pub fn compile_return(&mut self) {
-        // Skip return keyword
-        self.analyzer.advance();
-        if !(self.analyzer.token_type() == TokenType::Symbol && self.analyzer.symbol() == ';') {
-            self.compile_expression();
-        } else {
-            self.vm_writer.write_push(Segment::Const, 0);
+    pub fn compile_return(&mut self, span: &Span, value: Option<&Expression>) -> CompileResult<()> {
+        self.check_return(span, value)?;
+        match value {
+            Some(value) => self.compile_expression(value)?,
+            None => self.vm_writer.write_push(Segment::Const, 0),
         }
-        // Skip ;
-        self.analyzer.advance();
-        
         self.vm_writer.write_return();
+        Ok(())
     }
 
-    pub fn compile_if(&mut self) {
-        // Skip if keyword
-        self.analyzer.advance();
-        if self.analyzer.token_type() != TokenType::Symbol || self.analyzer.symbol() != '(' {
-            panic!("Missing expression for if statement");
-        }
-        // Skip (
-        self.analyzer.advance();
+    // Check that a return matches the return type of the subroutine, and
+    // that constructors return this
+    fn check_return(&self, span: &Span, value: Option<&Expression>) -> CompileResult<()> {
+        let error = match (value, self.return_type.as_ref()) {
+            (Some(value), None) =>
+                CompileError::semantic(value.span.clone(), format!(
+                    "{} is void but returns a value", self.subroutine_name))
+                    .with_code(E_RETURN_MISMATCH)
+                    .with_help("void subroutines end with return; without a value"),
+            (None, Some(return_type)) =>
+                CompileError::semantic(span.clone(), format!(
+                    "{} returns {} but this return has no value", self.subroutine_name, return_type))
+                    .with_code(E_RETURN_MISMATCH),
+            (Some(value), Some(_)) if self.subroutine_kind == SubroutineKind::Constructor => {
+                if let TermKind::KeywordConst(KeywordConst::This) = value.first.kind {
+                    if value.rest.is_empty() {
+                        return Ok(());
+                    }
+                }
+                CompileError::semantic(value.span.clone(), format!(
+                    "Constructor {} returns something other than this", self.subroutine_name))
+                    .with_code(E_CONSTRUCTOR_RETURN)
+                    .with_help("constructors end with return this;")
+            }
+            _ => return Ok(()),
+        };
+        Err(error)
+    }
 
+    pub fn compile_if(&mut self, condition: &Expression, then_body: &[Statement],
+                      else_body: Option<&Vec<Statement>>) -> CompileResult<()> {
         let if_label = "if".to_string() + &self.gen_label_num();
         let end_label = format!("{}end", if_label);
         let else_label = format!("{}else", if_label);
-        
+
         // Push result of expression to stack and skip to else if not true
         // TODO: do not skip to else if it does not exist
-        self.compile_expression();
+        if let Err(error) = self.compile_expression(condition) {
+            self.errors.push(error);
+        }
         self.vm_writer.write_arithmetic(Command::Not);
         self.vm_writer.write_if(&else_label);
 
-        if self.analyzer.token_type() != TokenType::Symbol || self.analyzer.symbol() != ')' {
-            panic!("Missing closing parenthesis for if expression");
-        }
-        // Skip )
-        self.analyzer.advance();
-
-        if self.analyzer.token_type() != TokenType::Symbol || self.analyzer.symbol() != '{' {
-            panic!("Missing opening brace on if statement");
-        }
-        // Skip {
-        self.analyzer.advance();
-
         // Write if part
-        self.compile_statements();
+        self.compile_statements(then_body);
         self.vm_writer.write_goto(&end_label);
 
-        // Skip closing brace
-        self.analyzer.advance();
-
         // Manage else part
         self.vm_writer.write_label(&else_label);
-        if self.analyzer.token_type() == TokenType::Keyword && self.analyzer.key_word() == Some(Keyword::Else) {
-            // Skip else keyword
-            self.analyzer.advance();
-
-            if self.analyzer.token_type() != TokenType::Symbol || self.analyzer.symbol() != '{' {
-                panic!("Missing opening brace on else statement");
-            }
-            self.analyzer.advance();
-            
-            // Compile statements in else part
-            self.compile_statements();
-
-            // Skip closing brace }
-            self.analyzer.advance();
+        if let Some(else_body) = else_body {
+            self.compile_statements(else_body);
         }
         self.vm_writer.write_label(&end_label);
+        Ok(())
     }
 
-    pub fn compile_expression(&mut self) {
-        let ops = [
-            '+', '-', '*', '/', '&',
-            '|', '<', '>', '=',
-        ];
-
+    pub fn compile_expression(&mut self, expression: &Expression) -> CompileResult<()> {
         // Push first term to stack
-        //println!("current fterm {}", make_tag_string(&self.analyzer));
-        self.compile_term();
-        while self.analyzer.token_type() == TokenType::Symbol && ops.contains(&self.analyzer.symbol()) {
-            //println!("yolo");
-            let sym = self.analyzer.symbol();
-            //println!("current symbol {}", make_tag_string(&self.analyzer));
-            self.analyzer.advance();
-
+        self.compile_term(&expression.first)?;
+        for &(op, ref term) in &expression.rest {
             // Push new term to stack and do calculation
-            self.compile_term();
-            if sym != '*' && sym != '/' {
-                self.vm_writer.write_arithmetic(symbol_to_command(sym));
-            } else if sym == '*' {
-                self.vm_writer.write_call("Math.multiply", 2);
-            } else if sym == '/' {
-                self.vm_writer.write_call("Math.divide", 2);
+            self.compile_term(term)?;
+            match op {
+                BinaryOp::Mul => self.write_os_call(&term.span, "*", "Math", "multiply", 2)?,
+                BinaryOp::Div => self.write_os_call(&term.span, "/", "Math", "divide", 2)?,
+                _ => self.vm_writer.write_arithmetic(op_to_command(op)),
             }
         }
+        Ok(())
     }
 
-    pub fn compile_term(&mut self) {
-        //println!("current term {}", make_tag_string(&self.analyzer));
-        let current_token_type = self.analyzer.token_type();
-        // Push constants directly
-        if current_token_type == TokenType::IntConst {
-            self.vm_writer.write_push(Segment::Const, self.analyzer.int_val());
-            self.analyzer.advance();
-        } else if current_token_type == TokenType::StringConst {
-            // Create a new string object and append all the characters
-            let string = self.analyzer.string_val();
-            self.vm_writer.write_push(Segment::Const, string.len() as i32);
-            self.vm_writer.write_call("String.new", 1);
-            for c in string.chars() {
-                self.vm_writer.write_push(Segment::Const, c as i32);
-                self.vm_writer.write_call("String.appendChar", 2);
+    pub fn compile_term(&mut self, term: &Term) -> CompileResult<()> {
+        match term.kind {
+            // Push constants directly
+            TermKind::IntConst(val) => self.vm_writer.write_push(Segment::Const, val),
+            TermKind::StringConst(ref string) => {
+                // Create a new string object and append all the characters
+                self.vm_writer.write_push(Segment::Const, string.chars().count() as i32);
+                self.write_os_call(&term.span, "a string constant", "String", "new", 1)?;
+                for c in string.chars() {
+                    // The lexer only lets through characters that Hack has
+                    self.vm_writer.write_push(Segment::Const, hack_char_code(c).unwrap_or(c as i32));
+                    self.write_os_call(&term.span, "a string constant", "String", "appendChar", 2)?;
+                }
             }
-            self.analyzer.advance();
-        } else if current_token_type == TokenType::Keyword {
-            let keyword = self.analyzer.key_word().unwrap();
-            if keyword == Keyword::This {
+            TermKind::KeywordConst(KeywordConst::This) => {
+                if self.subroutine_kind == SubroutineKind::Function {
+                    return Err(CompileError::semantic(term.span.clone(), format!(
+                        "Cannot use this in function {}", self.subroutine_name))
+                        .with_code(E_THIS_IN_FUNCTION)
+                        .with_help("only constructors and methods have a this"));
+                }
                 self.vm_writer.write_push(Segment::Pointer, 0);
-            } else if keyword == Keyword::True {
+            }
+            TermKind::KeywordConst(KeywordConst::True) => {
                 self.vm_writer.write_push(Segment::Const, 1);
                 self.vm_writer.write_arithmetic(Command::Neg);
-            } else {
-                let val = match keyword {
-                    Keyword::False => 0,
-                    Keyword::Null => 0,
-                    _ => panic!("Invalid keyword in expression")
-                };
-                self.vm_writer.write_push(Segment::Const, val);
-            }
-            self.analyzer.advance();
-        }
-        // Parse negation or inversion
-        else if current_token_type == TokenType::Symbol && '-' == self.analyzer.symbol() {
-            self.analyzer.advance();
-            self.compile_term();
-            self.vm_writer.write_arithmetic(Command::Neg);
-        } else if current_token_type == TokenType::Symbol && '~' == self.analyzer.symbol() {
-            self.analyzer.advance();
-            self.compile_term();
-            self.vm_writer.write_arithmetic(Command::Not);
-        }
-        // Parse sub-expression in ()
-        else if current_token_type == TokenType::Symbol && self.analyzer.symbol() == '(' {
-                // Skip (
-                self.analyzer.advance();
-
-                self.compile_expression();
-                
-                // Skip )
-                self.analyzer.advance();
-        } else {
-            // Parse expression that requires variable, function call or array
-            if self.analyzer.token_type() != TokenType::Identifier {
-                panic!(format!("Unexpected token inside expression term {}", make_tag_string(&self.analyzer)));
             }
-            
-            let name1 = self.analyzer.identifier();
-            self.analyzer.advance();
-
-            // Expecing one of [ . or something new
-            if self.analyzer.token_type() != TokenType::Symbol {
-                panic!("Weird shit is going on inside term. Expected symbol");
+            TermKind::KeywordConst(KeywordConst::False) | TermKind::KeywordConst(KeywordConst::Null) =>
+                self.vm_writer.write_push(Segment::Const, 0),
+            // Negation or inversion
+            TermKind::Unary(op, ref inner) => {
+                self.compile_term(inner)?;
+                match op {
+                    UnaryOp::Neg => self.vm_writer.write_arithmetic(Command::Neg),
+                    UnaryOp::Not => self.vm_writer.write_arithmetic(Command::Not),
+                }
             }
-
-            // Check if it's a function call or array
-            let next_symbol = self.analyzer.symbol();
-            if next_symbol == '(' || next_symbol == '.' {
-                self.analyzer.advance();
-                self.compile_function_call(next_symbol, name1);
-            } else if next_symbol == '[' {
-                self.analyzer.advance();
+            TermKind::Paren(ref expression) => self.compile_expression(expression)?,
+            TermKind::Call(ref call) => self.compile_subroutine_call(call)?,
+            TermKind::Index(ref name, ref index) => {
                 // Calculate address
-                self.compile_expression();
+                self.compile_expression(index)?;
 
-                // Skip ]
-                self.analyzer.advance();
-                
-                self.vm_writer.write_push(kind_to_segment(
-                    self.symbol_table.kind_of(&name1)), self.symbol_table.index_of(&name1));
+                let (seg, var_index) = self.variable(name)?;
+                self.vm_writer.write_push(seg, var_index);
                 self.vm_writer.write_arithmetic(Command::Add);
                 self.vm_writer.write_pop(Segment::Pointer, 1);
                 // Push content to stack
                 self.vm_writer.write_push(Segment::That, 0);
-            } else {
+            }
+            TermKind::Var(ref name) => {
                 // It's a simple variable, push it (like a boss!)
-                self.vm_writer.write_push(kind_to_segment(
-                    self.symbol_table.kind_of(&name1)), self.symbol_table.index_of(&name1));
+                let (seg, index) = self.variable(name)?;
+                self.vm_writer.write_push(seg, index);
             }
         }
+        Ok(())
     }
 
-    pub fn compile_expression_list(&mut self) -> i32 {
-        let mut n = 0;
-        while !(self.analyzer.token_type() == TokenType::Symbol && self.analyzer.symbol() == ')') {
-            self.compile_expression();
-            if self.analyzer.token_type() == TokenType::Symbol && self.analyzer.symbol() == ',' {
-                self.analyzer.advance();
-            }
-            n += 1;
+    pub fn compile_expression_list(&mut self, expressions: &[Expression]) -> CompileResult<i32> {
+        for expression in expressions {
+            self.compile_expression(expression)?;
         }
-        n
+        Ok(expressions.len() as i32)
     }
 }
@@ -0,0 +1,173 @@
+use error::*;
+use span::*;
+
+use std::collections::HashMap;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const GREEN: &str = "\x1b[1;32m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+
+/// Renders errors in the same style as rustc, with the offending source line
+/// and the span underlined with carets
+pub struct Emitter {
+    color: bool,
+    // The lines of every source file that has been registered, by file name
+    sources: HashMap<String, Vec<String>>,
+}
+
+impl Emitter {
+    pub fn new(color: bool) -> Emitter {
+        Emitter {
+            color,
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Make the source of a file available for snippets. Spans in files that
+    /// have not been added are shown without the source line.
+    pub fn add_source(&mut self, file: &str, source: &str) {
+        let lines = source.lines().map(|line| line.to_string()).collect();
+        self.sources.insert(file.to_string(), lines);
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn render(&self, error: &CompileError) -> String {
+        let mut out = String::new();
+
+        let title = format!("{} {}[{}]", kind_string(error.kind), severity_string(error.severity), error.code);
+        let style = match error.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+        out += &format!("{}{}\n", self.paint(style, &title), self.paint(BOLD, &format!(": {}", error.message)));
+
+        // Width of the line number column, large enough for every snippet
+        let mut spans: Vec<&Span> = error.notes.iter().filter_map(|note| note.span.as_ref()).collect();
+        spans.extend(error.span.iter());
+        let width = spans.iter().map(|span| span.start.line.to_string().len()).max().unwrap_or(1);
+
+        if let Some(ref span) = error.span {
+            out += &self.snippet(span, '^', style, width);
+        }
+
+        for note in &error.notes {
+            match note.span {
+                Some(ref span) => {
+                    out += &format!("{}: {}\n", self.paint(GREEN, "note"), note.message);
+                    out += &self.snippet(span, '-', BLUE, width);
+                }
+                None => {
+                    out += &format!("{} {} {}: {}\n", " ".repeat(width),
+                                    self.paint(BLUE, "="), self.paint(BOLD, "note"), note.message);
+                }
+            }
+        }
+
+        for help in &error.help {
+            out += &format!("{} {} {}: {}\n", " ".repeat(width),
+                            self.paint(BLUE, "="), self.paint(CYAN, "help"), help);
+        }
+
+        out
+    }
+
+    // Show the first line of a span with the span underlined
+    fn snippet(&self, span: &Span, underline: char, style: &str, width: usize) -> String {
+        let gutter = self.paint(BLUE, &format!("{} |", " ".repeat(width)));
+        let mut out = format!("{}{} {}\n", " ".repeat(width), self.paint(BLUE, "-->"), span);
+
+        let line = match self.sources.get(&*span.file).and_then(|lines| lines.get(span.start.line - 1)) {
+            Some(line) => line,
+            None => return out,
+        };
+
+        // Keep tabs in the indentation so the underline lines up with the source
+        let prefix: String = line.chars().take(span.start.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let line_length = line.chars().count();
+        let end_column = if span.end.line == span.start.line {
+            span.end.column
+        } else {
+            line_length + 1
+        };
+        let length = end_column.saturating_sub(span.start.column).max(1);
+        let marks = underline.to_string().repeat(length);
+
+        let line_number = self.paint(BLUE, &format!("{:>width$} |", span.start.line, width = width));
+        out += &format!("{}\n", gutter);
+        out += &format!("{} {}\n", line_number, line);
+        out += &format!("{} {}{}\n", gutter, prefix, self.paint(style, &marks));
+        out
+    }
+}
+
+// Quote and escape a string for JSON
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_location(location: &Location) -> String {
+    format!("{{\"line\":{},\"column\":{},\"offset\":{}}}",
+            location.line, location.column, location.offset)
+}
+
+// The file and span fields of a diagnostic or related span
+fn json_span_fields(span: Option<&Span>) -> String {
+    match span {
+        Some(span) => format!("\"file\":{},\"span\":{{\"start\":{},\"end\":{}}}",
+                              json_string(&span.file), json_location(&span.start), json_location(&span.end)),
+        None => "\"file\":null,\"span\":null".to_string(),
+    }
+}
+
+// Name of the error kind for machine readable output
+fn kind_id(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::Io => "io",
+        ErrorKind::Lexical => "lexical",
+        ErrorKind::Syntax => "syntax",
+        ErrorKind::Semantic => "semantic",
+    }
+}
+
+/// Format an error as a single line of JSON, for tools that read diagnostics
+pub fn to_json(error: &CompileError) -> String {
+    let related: Vec<String> = error.notes.iter().map(|note| {
+        format!("{{\"message\":{},{}}}", json_string(&note.message), json_span_fields(note.span.as_ref()))
+    }).collect();
+    let help: Vec<String> = error.help.iter().map(|help| json_string(help)).collect();
+
+    format!("{{\"severity\":{},\"kind\":{},\"code\":{},\"message\":{},{},\"related\":[{}],\"help\":[{}]}}",
+            json_string(severity_string(error.severity)),
+            json_string(kind_id(error.kind)),
+            json_string(error.code),
+            json_string(&error.message),
+            json_span_fields(error.span.as_ref()),
+            related.join(","),
+            help.join(","))
+}
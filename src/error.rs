@@ -0,0 +1,166 @@
+use span::Span;
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Which stage of the compiler an error comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    Io,
+    Lexical,
+    Syntax,
+    Semantic,
+}
+
+/// Whether a diagnostic stops the compilation. Warnings are reported but
+/// the output is still written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// Codes identifying each kind of error in diagnostics. The first digit
+// after the E is the stage the error comes from, and codes ending in 00 are
+// used for errors that do not have a more specific code.
+pub const E_IO: &str = "E0001";
+pub const E_LEXICAL: &str = "E0100";
+pub const E_UNEXPECTED_CHAR: &str = "E0101";
+pub const E_UNTERMINATED_STRING: &str = "E0102";
+pub const E_INT_RANGE: &str = "E0103";
+pub const E_UNTERMINATED_COMMENT: &str = "E0104";
+pub const E_INVALID_NUMBER: &str = "E0105";
+pub const E_UNSUPPORTED_CHAR: &str = "E0106";
+pub const E_UNKNOWN_ESCAPE: &str = "E0107";
+pub const E_SYNTAX: &str = "E0200";
+pub const E_UNEXPECTED_EOF: &str = "E0201";
+pub const E_SEMANTIC: &str = "E0300";
+pub const E_UNDEFINED_VARIABLE: &str = "E0301";
+pub const E_NOT_AN_OBJECT: &str = "E0302";
+pub const E_DUPLICATE_DEFINITION: &str = "E0303";
+pub const E_UNDEFINED_SUBROUTINE: &str = "E0304";
+pub const E_TYPE_MISMATCH: &str = "E0305";
+pub const E_INVALID_OPERAND: &str = "E0306";
+pub const E_NOT_AN_ARRAY: &str = "E0307";
+pub const E_VOID_VALUE: &str = "E0308";
+pub const E_INVALID_CALL: &str = "E0309";
+pub const E_ARGUMENT_COUNT: &str = "E0310";
+pub const E_MISSING_RETURN: &str = "E0311";
+pub const E_RETURN_MISMATCH: &str = "E0312";
+pub const E_CONSTRUCTOR_RETURN: &str = "E0313";
+pub const E_UNDEFINED_CLASS: &str = "E0314";
+pub const E_THIS_IN_FUNCTION: &str = "E0315";
+
+/// Extra information attached to an error, optionally pointing at another
+/// place in the source such as where a variable was declared
+#[derive(Clone, Debug)]
+pub struct Note {
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct CompileError {
+    pub kind: ErrorKind,
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    // I/O errors are not tied to a place in the source
+    pub span: Option<Span>,
+    pub notes: Vec<Note>,
+    pub help: Vec<String>,
+}
+
+pub type CompileResult<T> = Result<T, CompileError>;
+
+pub fn kind_string(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::Io => "I/O",
+        ErrorKind::Lexical => "lexical",
+        ErrorKind::Syntax => "syntax",
+        ErrorKind::Semantic => "semantic",
+    }
+}
+
+pub fn severity_string(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    }
+}
+
+impl CompileError {
+    pub fn io(path: &str, error: &io::Error) -> CompileError {
+        CompileError::new(ErrorKind::Io, None, format!("{}: {}", path, error))
+    }
+
+    pub fn lexical<S: Into<String>>(span: Span, message: S) -> CompileError {
+        CompileError::new(ErrorKind::Lexical, Some(span), message.into())
+    }
+
+    pub fn syntax<S: Into<String>>(span: Span, message: S) -> CompileError {
+        CompileError::new(ErrorKind::Syntax, Some(span), message.into())
+    }
+
+    pub fn semantic<S: Into<String>>(span: Span, message: S) -> CompileError {
+        CompileError::new(ErrorKind::Semantic, Some(span), message.into())
+    }
+
+    fn new(kind: ErrorKind, span: Option<Span>, message: String) -> CompileError {
+        let code = match kind {
+            ErrorKind::Io => E_IO,
+            ErrorKind::Lexical => E_LEXICAL,
+            ErrorKind::Syntax => E_SYNTAX,
+            ErrorKind::Semantic => E_SEMANTIC,
+        };
+        CompileError {
+            kind,
+            severity: Severity::Error,
+            code,
+            message,
+            span,
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    /// Use a more specific error code than the default for the kind
+    pub fn with_code(mut self, code: &'static str) -> CompileError {
+        self.code = code;
+        self
+    }
+
+    /// Report the error as a warning or the other way round
+    pub fn with_severity(mut self, severity: Severity) -> CompileError {
+        self.severity = severity;
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Add a note pointing at another place in the source
+    pub fn with_note<S: Into<String>>(mut self, span: Option<Span>, message: S) -> CompileError {
+        self.notes.push(Note { span, message: message.into() });
+        self
+    }
+
+    /// Add a suggestion for how to fix the error
+    pub fn with_help<S: Into<String>>(mut self, message: S) -> CompileError {
+        self.help.push(message.into());
+        self
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref span) = self.span {
+            write!(f, "{}: ", span)?;
+        }
+        write!(f, "{} {}[{}]: {}", kind_string(self.kind), severity_string(self.severity), self.code, self.message)
+    }
+}
+
+impl Error for CompileError {}
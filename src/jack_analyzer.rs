@@ -1,17 +1,36 @@
-use std::error::Error;
+use error::*;
+use span::*;
+use vfs::SourceProvider;
+
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::Path;
-use std::collections::HashSet;
+use std::rc::Rc;
 
+/// The tokens of a source file. The whole file is tokenized when the
+/// analyzer is created, and advance steps through the tokens one at a time.
 pub struct JackAnalyzer {
-    data: Vec<char>,
+    source: String,
+    file: Rc<str>,
+    tokens: Vec<Token>,
+    // The lexical error of each token, if it has one. They are reported by
+    // advance when it reaches the token.
+    errors: Vec<Option<CompileError>>,
+    // Index of the current token, tokens.len() once advance has run past
+    // the last one
     pos: usize,
-    first_time: bool,
-    symbols: HashSet<char>,
+    started: bool,
+    // An error found after the last token, such as an unterminated
+    // comment. It is reported instead of the end of the file.
+    end_error: Option<CompileError>,
+    // The whitespace and comments after the last token
+    end_trivia: Vec<Trivia>,
+    string_escapes: bool,
+    // Where the file ends
+    end: Location,
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TokenType {
     Keyword,
     Symbol,
@@ -20,7 +39,7 @@ pub enum TokenType {
     StringConst,
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Keyword {
     Class,
     Method,
@@ -45,225 +64,529 @@ pub enum Keyword {
     This,
 }
 
-// TODO: add function to print current line with line number
-impl JackAnalyzer {
-    pub fn new(path: &Path) -> JackAnalyzer {
-        let display = path.display();
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+    // A block comment starting with /**, used for API documentation
+    DocComment,
+}
 
-        let mut file = match File::open(&path) {
-            Err(why) => panic!("Couldn't open file {}: {}", display,
-                               Error::description(&why)),
-            Ok(file) => file,
-        };
+/// Whitespace or a comment between tokens, which the parser does not see
+/// but which tools working on the source text need
+#[derive(Clone, Debug)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
 
-        let mut data_string = String::new();
-        match file.read_to_string(&mut data_string) {
-            Err(why) => panic!("couldn't read {}: {}", display,
-                               Error::description(&why)),
-            Ok(string) => string,
-        };
+/// A token and the text it was read from. The text of a string constant
+/// includes its quotes. `leading` is the whitespace and comments between
+/// the previous token and this one, so the source of a file is the leading
+/// trivia and text of each token followed by the trivia at the end of the
+/// file.
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub kind: TokenType,
+    pub text: String,
+    pub span: Span,
+    pub leading: Vec<Trivia>,
+}
 
-        JackAnalyzer {
-            data: data_string.chars().collect(),
-            pos: 0,
-            first_time: true,
-            symbols: [
-                '{', '}', '(', ')', '[', ']', '.',
-                ',', ';', '+', '-', '*', '/', '&',
-                '|', '<', '>', '=', '~',
-            ].iter().cloned().collect(),
+impl Token {
+    /// The text of the doc comment right before this token, if there is one
+    pub fn doc_comment(&self) -> Option<&str> {
+        let last_comment = self.leading.iter().rev().find(|trivia| trivia.kind != TriviaKind::Whitespace)?;
+        if last_comment.kind == TriviaKind::DocComment {
+            Some(&last_comment.text)
+        } else {
+            None
         }
     }
 
-    fn start_of_token(&self, pos: usize) -> bool {
-        if pos >= self.data.len() {
-            return false;
+    pub fn keyword(&self) -> Option<Keyword> {
+        if self.kind != TokenType::Keyword {
+            return None;
         }
+        keyword_from_str(&self.text)
+    }
 
-        if self.data[pos].is_whitespace() {
-            return false;
-        }
+    pub fn symbol(&self) -> char {
+        self.text.chars().next().unwrap_or(' ')
+    }
+
+    pub fn int_val(&self) -> i32 {
+        // Constants that do not fit have already been reported by the lexer
+        self.text.parse().unwrap_or(0)
+    }
 
-        if self.data[pos] == '/' {
-            if pos + 1 >= self.data.len() {
-                return true;
+    /// The contents of a string constant without the quotes, as written
+    pub fn string_val(&self) -> &str {
+        let text = &self.text[1..];
+        text.strip_suffix('"').unwrap_or(text)
+    }
+}
+
+pub fn keyword_from_str(name: &str) -> Option<Keyword> {
+    match name {
+        "class" => Some(Keyword::Class),
+        "method" => Some(Keyword::Method),
+        "function" => Some(Keyword::Function),
+        "constructor" => Some(Keyword::Constructor),
+        "int" => Some(Keyword::Int),
+        "boolean" => Some(Keyword::Boolean),
+        "char" => Some(Keyword::Char),
+        "void" => Some(Keyword::Void),
+        "var" => Some(Keyword::Var),
+        "static" => Some(Keyword::Static),
+        "field" => Some(Keyword::Field),
+        "let" => Some(Keyword::Let),
+        "do" => Some(Keyword::Do),
+        "if" => Some(Keyword::If),
+        "else" => Some(Keyword::Else),
+        "while" => Some(Keyword::While),
+        "return" => Some(Keyword::Return),
+        "true" => Some(Keyword::True),
+        "false" => Some(Keyword::False),
+        "null" => Some(Keyword::Null),
+        "this" => Some(Keyword::This),
+        _ => None,
+    }
+}
+
+fn is_symbol(c: char) -> bool {
+    "{}()[].,;+-*/&|<>=~".contains(c)
+}
+
+// Identifiers are ASCII letters, digits and underscores, and do not start
+// with a digit
+fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Extensions to the Jack language that the lexer can accept
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LexOptions {
+    /// Allow the escapes \n, \t, \\ and \" in string constants. Hack has no
+    /// tab, so \t is a space.
+    pub string_escapes: bool,
+}
+
+/// The code of a character in the Hack character set, which is what
+/// String.appendChar and Output.printChar take. Newlines are 128, and Hack
+/// has no tab so tabs are spaces.
+pub fn hack_char_code(c: char) -> Option<i32> {
+    match c {
+        ' '..='~' => Some(c as i32),
+        '\n' => Some(128),
+        '\t' => Some(32),
+        _ => None,
+    }
+}
+
+// Replace the escapes in the contents of a string constant with the
+// characters they stand for. Unknown escapes have been reported by the
+// lexer and are kept as they are.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some(c @ '\\') | Some(c @ '"') => unescaped.push(c),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
             }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
 
-            let next_char = self.data[pos+1];
-            if next_char == '/' || next_char == '*' {
-                return false;
+// Reads the tokens of a source in a single pass over it, following the
+// lexical grammar of the Jack language specification
+struct Lexer<'a> {
+    source: &'a str,
+    file: Rc<str>,
+    location: Location,
+    tokens: Vec<Token>,
+    errors: Vec<Option<CompileError>>,
+    // An error that is not about a token, reported at the end of the file
+    end_error: Option<CompileError>,
+    end_trivia: Vec<Trivia>,
+    string_escapes: bool,
+}
+
+impl<'a> Lexer<'a> {
+    fn peek_char(&self) -> Option<char> {
+        self.source[self.location.offset..].chars().next()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        self.source[self.location.offset..].starts_with(text)
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek_char() {
+            self.location.offset += c.len_utf8();
+            if c == '\n' {
+                self.location.line += 1;
+                self.location.column = 1;
+            } else {
+                self.location.column += 1;
             }
         }
-        return true;
     }
 
-    fn skip_comments_and_whitespace(&self, start_pos: usize) -> Option<usize> {
-        let mut peek_pos = start_pos;
-        while peek_pos < self.data.len() {
-            // Skip comments
-            if self.data[peek_pos] == '/' {
-                if peek_pos + 1 >= self.data.len() {
-                    return Some(peek_pos);
+    fn bump_while<F: Fn(char) -> bool>(&mut self, pred: F) {
+        while self.peek_char().is_some_and(&pred) {
+            self.bump();
+        }
+    }
+
+    // Read the whitespace and comments up to the next token or the end of
+    // the file
+    fn read_trivia(&mut self) -> Vec<Trivia> {
+        let mut trivia = Vec::new();
+        loop {
+            let start = self.location;
+            let kind = if self.starts_with("//") {
+                self.bump_while(|c| c != '\n');
+                TriviaKind::LineComment
+            } else if self.starts_with("/*") {
+                let kind = if self.starts_with("/**") && !self.starts_with("/**/") {
+                    TriviaKind::DocComment
+                } else {
+                    TriviaKind::BlockComment
+                };
+                self.bump();
+                self.bump();
+                while self.peek_char().is_some() && !self.starts_with("*/") {
+                    self.bump();
                 }
+                if self.peek_char().is_none() {
+                    let opening = Location { column: start.column + 2, offset: start.offset + 2, ..start };
+                    self.end_error = Some(
+                        CompileError::lexical(Span::new(self.file.clone(), start, opening), "Unterminated comment")
+                            .with_code(E_UNTERMINATED_COMMENT)
+                            .with_help("comments starting with /* must end with */"));
+                }
+                self.bump();
+                self.bump();
+                kind
+            } else if self.peek_char().is_some_and(|c| c.is_ascii_whitespace()) {
+                self.bump_while(|c| c.is_ascii_whitespace());
+                TriviaKind::Whitespace
+            } else {
+                return trivia;
+            };
+
+            trivia.push(Trivia {
+                kind,
+                text: self.source[start.offset..self.location.offset].to_string(),
+                span: Span::new(self.file.clone(), start, self.location),
+            });
+        }
+    }
 
-                // Skip multiline comments
-                if self.data[peek_pos+1] == '/' {
-                    while self.data[peek_pos] != '\n' {
-                        peek_pos += 1;
-                        if peek_pos >= self.data.len() {
-                            return None;
+    fn error(&self, start: Location, code: &'static str, message: String, help: &str) -> CompileError {
+        CompileError::lexical(Span::new(self.file.clone(), start, self.location), message)
+            .with_code(code)
+            .with_help(help)
+    }
+
+    // Read the rest of a string constant after the opening quote. Every
+    // character must be in the Hack character set, and the error is about
+    // the first one that is not.
+    fn read_string(&mut self, start: Location) -> Option<CompileError> {
+        let mut error = None;
+        loop {
+            let char_start = self.location;
+            match self.peek_char() {
+                // Strings cannot contain newlines, so unterminated strings
+                // end at the end of the line
+                None | Some('\n') | Some('\r') => {
+                    return Some(self.error(start, E_UNTERMINATED_STRING, "Unterminated string constant".to_string(),
+                                           "string constants end with \" on the same line"));
+                }
+                Some('"') => {
+                    self.bump();
+                    return error;
+                }
+                Some('\\') if self.string_escapes => {
+                    self.bump();
+                    match self.peek_char() {
+                        Some('n') | Some('t') | Some('\\') | Some('"') => self.bump(),
+                        // Leave the end of the line to be reported as unterminated
+                        None | Some('\n') | Some('\r') => (),
+                        Some(c) => {
+                            self.bump();
+                            if error.is_none() {
+                                error = Some(self.error(char_start, E_UNKNOWN_ESCAPE,
+                                                        format!("Unknown escape sequence \\{}", c),
+                                                        "the escapes are \\n, \\t, \\\\ and \\\""));
+                            }
                         }
                     }
-                } else if self.data[peek_pos+1] == '*' {
-                    while !(self.data[peek_pos] == '*' && self.data[peek_pos+1] == '/') {
-                        peek_pos += 1;
-                        if peek_pos + 1 >= self.data.len() {
-                            return None;
-                        }
+                }
+                Some(c) => {
+                    self.bump();
+                    if error.is_none() && !(' '..='~').contains(&c) {
+                        let help = if c == '\t' && self.string_escapes {
+                            "write tabs as \\t"
+                        } else {
+                            "Hack can only show the ASCII characters from space to ~"
+                        };
+                        error = Some(self.error(char_start, E_UNSUPPORTED_CHAR,
+                                                format!("Character {:?} is not in the Hack character set", c),
+                                                help));
                     }
-                    peek_pos += 2;
                 }
             }
+        }
+    }
 
-            // Skip whitespace
-            while self.data[peek_pos].is_whitespace() {
-                peek_pos += 1;
-                if peek_pos >= self.data.len() {
-                    return None;
-                }
+    // Read the next token and check that it is one the parser can handle
+    fn read_token(&mut self, c: char) -> (TokenType, Option<CompileError>) {
+        let start = self.location;
+        self.bump();
+        if is_symbol(c) {
+            (TokenType::Symbol, None)
+        } else if c == '"' {
+            (TokenType::StringConst, self.read_string(start))
+        } else if c.is_ascii_digit() {
+            self.bump_while(|c| c.is_ascii_digit());
+            if self.peek_char().is_some_and(|c| is_identifier_char(c) || c.is_alphabetic()) {
+                self.bump_while(|c| is_identifier_char(c) || c.is_alphabetic());
+                let text = &self.source[start.offset..self.location.offset];
+                return (TokenType::IntConst,
+                        Some(self.error(start, E_INVALID_NUMBER, format!("Invalid integer constant {}", text),
+                                        "identifiers cannot start with a digit")));
             }
-
-            if self.start_of_token(peek_pos) {
-                return Some(peek_pos);
+            let digits = &self.source[start.offset..self.location.offset];
+            if digits.parse::<u32>().map_or(true, |n| n > 32767) {
+                (TokenType::IntConst,
+                 Some(self.error(start, E_INT_RANGE, format!("Integer constant {} is out of range", digits),
+                                 "integer constants must be between 0 and 32767")))
+            } else {
+                (TokenType::IntConst, None)
             }
+        } else if is_identifier_start(c) || c.is_alphabetic() {
+            // Read letters outside ASCII as part of the identifier so the
+            // error is about the whole name
+            self.bump_while(|c| is_identifier_char(c) || c.is_alphanumeric());
+            let text = &self.source[start.offset..self.location.offset];
+            if let Some(bad) = text.chars().find(|&c| !is_identifier_char(c)) {
+                return (TokenType::Identifier,
+                        Some(self.error(start, E_UNEXPECTED_CHAR,
+                                        format!("Unexpected character '{}' in identifier {}", bad, text),
+                                        "identifiers may only contain ASCII letters, digits and _")));
+            }
+            match keyword_from_str(text) {
+                Some(_) => (TokenType::Keyword, None),
+                None => (TokenType::Identifier, None),
+            }
+        } else {
+            let span = Span::new(self.file.clone(), start, self.location);
+            (TokenType::Symbol,
+             Some(CompileError::lexical(span, format!("Unexpected character '{}'", c)).with_code(E_UNEXPECTED_CHAR)))
         }
-        return None;
     }
 
-    fn pos_of_next_token(&self) -> Option<usize> {
-        let mut peek_pos = self.pos;
-        
-        if !self.first_time {
-            peek_pos += 1;
+    fn tokenize(mut self) -> Lexer<'a> {
+        loop {
+            let leading = self.read_trivia();
+            let c = match self.peek_char() {
+                Some(c) => c,
+                None => {
+                    self.end_trivia = leading;
+                    break;
+                }
+            };
+
+            let start = self.location;
+            let (kind, error) = self.read_token(c);
+            self.errors.push(error);
+            self.tokens.push(Token {
+                kind,
+                text: self.source[start.offset..self.location.offset].to_string(),
+                span: Span::new(self.file.clone(), start, self.location),
+                leading,
+            });
         }
+        self
+    }
+}
 
-        if self.data[self.pos] == '\"' {
-            while self.data[peek_pos] != '\"' {
-                peek_pos += 1;
-            }
-            peek_pos += 1;
-        } else if peek_pos > 0 && self.data[self.pos].is_alphanumeric() {
-            while self.data[peek_pos].is_alphanumeric() {
-                peek_pos += 1;
-                if peek_pos >= self.data.len() {
-                    return None;
-                }
-            }
+impl JackAnalyzer {
+    pub fn new(path: &Path) -> CompileResult<JackAnalyzer> {
+        let display = path.display().to_string();
+
+        match File::open(path) {
+            Err(why) => Err(CompileError::io(&display, &why)),
+            Ok(file) => JackAnalyzer::from_reader(&display, file),
         }
+    }
 
-        let skip_result = self.skip_comments_and_whitespace(peek_pos);
-        return skip_result;
+    /// Read a file from a source provider, such as a zip archive
+    pub fn open(provider: &dyn SourceProvider, path: &Path, options: LexOptions) -> CompileResult<JackAnalyzer> {
+        let display = path.display().to_string();
+        match provider.read(path) {
+            Ok(source) => Ok(JackAnalyzer::from_source_with(&display, &source, options)),
+            Err(why) => Err(CompileError::io(&display, &why)),
+        }
     }
 
-    #[allow(dead_code)]
-    pub fn has_more_tokens(&self) -> bool {
-        match self.pos_of_next_token() {
-            Some(_) => true,
-            None => false,
+    /// Read the whole source from a reader. `name` is the file name used in
+    /// spans.
+    pub fn from_reader<R: Read>(name: &str, mut reader: R) -> CompileResult<JackAnalyzer> {
+        let mut data_string = String::new();
+        if let Err(why) = reader.read_to_string(&mut data_string) {
+            return Err(CompileError::io(name, &why));
         }
+        Ok(JackAnalyzer::from_source(name, &data_string))
     }
 
-    pub fn advance(&mut self) {
-        self.pos = self.pos_of_next_token().unwrap();
-        self.first_time = false;
+    pub fn from_source(name: &str, source: &str) -> JackAnalyzer {
+        JackAnalyzer::from_source_with(name, source, LexOptions::default())
     }
 
-    pub fn token_type(&self) -> TokenType {
-        let current_char = self.data[self.pos];
+    /// Tokenize a source with extensions to the language turned on
+    pub fn from_source_with(name: &str, source: &str, options: LexOptions) -> JackAnalyzer {
+        let file: Rc<str> = Rc::from(name);
+        let lexer = Lexer {
+            source,
+            file: file.clone(),
+            location: Location { line: 1, column: 1, offset: 0 },
+            tokens: Vec::new(),
+            errors: Vec::new(),
+            end_error: None,
+            end_trivia: Vec::new(),
+            string_escapes: options.string_escapes,
+        }.tokenize();
+        JackAnalyzer {
+            source: source.to_string(),
+            file,
+            tokens: lexer.tokens,
+            errors: lexer.errors,
+            end_error: lexer.end_error,
+            end_trivia: lexer.end_trivia,
+            string_escapes: options.string_escapes,
+            pos: 0,
+            started: false,
+            end: lexer.location,
+        }
+    }
 
-        if self.symbols.contains(&current_char) {
-            return TokenType::Symbol;
+    pub fn has_more_tokens(&self) -> bool {
+        if self.started {
+            self.pos + 1 < self.tokens.len()
+        } else {
+            !self.tokens.is_empty()
         }
+    }
+
+    /// The full text of the source file
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// True once advance has run past the last token
+    pub fn at_eof(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    /// Every token in the file
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// The whitespace and comments after the last token
+    pub fn end_trivia(&self) -> &[Trivia] {
+        &self.end_trivia
+    }
+
+    /// The current token. Must not be called at the end of the file.
+    pub fn token(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
 
-        if current_char == '\"' {
-            return TokenType::StringConst;
+    /// The token `n` places after the current one, so peek(0) is the current
+    /// token. Before the first advance, peek(1) is the first token.
+    pub fn peek(&self, n: usize) -> Option<&Token> {
+        let index = if self.started { self.pos + n } else { (self.pos + n).checked_sub(1)? };
+        self.tokens.get(index)
+    }
+
+    /// Where the current token is in the source file
+    pub fn span(&self) -> Span {
+        if !self.started {
+            let start = Location { line: 1, column: 1, offset: 0 };
+            return Span::new(self.file.clone(), start, start);
+        }
+        match self.tokens.get(self.pos) {
+            Some(token) => token.span.clone(),
+            None => Span::new(self.file.clone(), self.end, self.end),
         }
+    }
 
-        if current_char.is_numeric() {
-            return TokenType::IntConst;
+    pub fn advance(&mut self) -> CompileResult<()> {
+        if self.started {
+            self.pos = (self.pos + 1).min(self.tokens.len());
         }
+        self.started = true;
 
-        if current_char.is_alphabetic() {
-            return match self.key_word() {
-                Some(_) => TokenType::Keyword,
-                None => TokenType::Identifier,
+        if self.at_eof() {
+            if let Some(error) = self.end_error.take() {
+                return Err(error);
             }
+            return Err(CompileError::syntax(self.span(), "Unexpected end of file")
+                       .with_code(E_UNEXPECTED_EOF));
+        }
+        match self.errors[self.pos].take() {
+            Some(error) => Err(error),
+            None => Ok(()),
         }
+    }
 
-        panic!("{} was nothing", current_char)
+    pub fn token_type(&self) -> TokenType {
+        self.token().kind
     }
 
     pub fn key_word(&self) -> Option<Keyword> {
-        let name = self.identifier();
-        return match &*name {
-            "class" => Some(Keyword::Class),
-            "method" => Some(Keyword::Method),
-            "function" => Some(Keyword::Function),
-            "constructor" => Some(Keyword::Constructor),
-            "int" => Some(Keyword::Int),
-            "boolean" => Some(Keyword::Boolean),
-            "char" => Some(Keyword::Char),
-            "void" => Some(Keyword::Void),
-            "var" => Some(Keyword::Var),
-            "static" => Some(Keyword::Static),
-            "field" => Some(Keyword::Field),
-            "let" => Some(Keyword::Let),
-            "do" => Some(Keyword::Do),
-            "if" => Some(Keyword::If),
-            "else" => Some(Keyword::Else),
-            "while" => Some(Keyword::While),
-            "return" => Some(Keyword::Return),
-            "true" => Some(Keyword::True),
-            "false" => Some(Keyword::False),
-            "null" => Some(Keyword::Null),
-            "this" => Some(Keyword::This),
-            _ => None,
-        }
+        self.token().keyword()
     }
 
     pub fn symbol(&self) -> char {
-        self.data[self.pos]
+        self.token().symbol()
     }
 
     pub fn identifier(&self) -> String {
-        let mut buf = String::new();
-        let mut peek_pos = self.pos;
-        while self.data[peek_pos].is_alphanumeric() {
-            buf.push(self.data[peek_pos]);
-            peek_pos += 1;
-        }
-        return buf;
+        self.token().text.clone()
     }
 
     pub fn int_val(&self) -> i32 {
-        let mut buf = String::new();
-        let mut peek_pos = self.pos;
-        while self.data[peek_pos].is_numeric() {
-            buf.push(self.data[peek_pos]);
-            peek_pos += 1;
-        }
-        return buf.parse::<i32>().unwrap();
+        self.token().int_val()
     }
 
+    /// The characters of a string constant, with any escapes replaced
     pub fn string_val(&self) -> String {
-        let mut peek_pos = self.pos + 1;
-        let mut buf = String::new();
-
-        while self.data[peek_pos] != '"' {
-            buf.push(self.data[peek_pos]);
-            peek_pos += 1;
+        if self.string_escapes {
+            unescape(self.token().string_val())
+        } else {
+            self.token().string_val().to_string()
         }
-
-        return buf;
     }
 }
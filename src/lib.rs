@@ -0,0 +1,297 @@
+// Errors carry spans and notes and are only created on the slow path
+#![allow(clippy::result_large_err)]
+
+//! A compiler from the Jack language of nand2tetris to VM code.
+//!
+//! `compile_source` compiles a class held in memory:
+//!
+//! ```
+//! let output = jackcompiler::compile_source("Main.jack", "
+//!     class Main {
+//!         function void main() {
+//!             return;
+//!         }
+//!     }").unwrap();
+//! assert_eq!(output.class_name, "Main");
+//! assert!(output.code.starts_with("function Main.main 0\n"));
+//! ```
+//!
+//! `compile_project` compiles several classes together, checking the calls
+//! between them and to the Jack OS described in `os`. `compile_sources` is
+//! the driver behind both and the command line compiler, and can also write
+//! the XML files of project 10.
+//!
+//! The modules give access to each stage on its own: the tokenizer in
+//! `jack_analyzer`, the syntax tree built by `parser`, the `symbol_table`,
+//! the index of the classes in a project in `class_index`, the
+//! `type_checker` and the code generation in `compilation_engine` and
+//! `vm_writer`. The `vfs` module lets
+//! the compiler read from and write to memory or zip archives instead of
+//! the file system.
+
+extern crate zip;
+
+pub mod jack_analyzer;
+pub mod ast;
+pub mod parser;
+pub mod visit;
+pub mod compilation_engine;
+pub mod xml_output;
+pub mod symbol_table;
+pub mod type_checker;
+pub mod class_index;
+pub mod os;
+pub mod vm_writer;
+pub mod span;
+pub mod error;
+pub mod diagnostic;
+pub mod suggest;
+pub mod vfs;
+
+use class_index::ClassIndex;
+use compilation_engine::CompilationEngine;
+use diagnostic::Emitter;
+use error::CompileError;
+use jack_analyzer::{JackAnalyzer, LexOptions};
+use parser::Parser;
+use type_checker::TypeChecker;
+use vfs::{MemorySink, OutputSink};
+
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+/// What to generate for each source
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Emit {
+    Vm,
+    /// The tokens, like the XxxT.xml files of project 10
+    TokensXml,
+    /// The parse tree, like the Xxx.xml files of project 10
+    Xml,
+    /// The parse tree with the symbol table entry of each identifier
+    AnnotatedXml,
+}
+
+impl Emit {
+    /// The kind of output with the name used by --emit
+    pub fn parse(name: &str) -> Option<Emit> {
+        match name {
+            "vm" => Some(Emit::Vm),
+            "tokens-xml" => Some(Emit::TokensXml),
+            "xml" => Some(Emit::Xml),
+            "annotated-xml" => Some(Emit::AnnotatedXml),
+            _ => None,
+        }
+    }
+
+    /// What to add to the file stem to get the name of the output file
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Emit::Vm => ".vm",
+            Emit::TokensXml => "T.xml",
+            Emit::Xml | Emit::AnnotatedXml => ".xml",
+        }
+    }
+}
+
+/// The result of compiling a single source with compile_sources
+#[derive(Clone, Debug)]
+pub struct SourceOutput {
+    /// The name of the class, if the source could be parsed
+    pub class_name: Option<String>,
+    /// The VM code or XML, only if there were no errors
+    pub output: Option<String>,
+    /// The errors and warnings about the source, in the order they were found
+    pub diagnostics: Vec<CompileError>,
+}
+
+/// The VM code generated for a class and the warnings about it
+#[derive(Clone, Debug)]
+pub struct VmOutput {
+    pub class_name: String,
+    pub code: String,
+    pub warnings: Vec<CompileError>,
+}
+
+/// Settings for compile_source_with and compile_project
+#[derive(Clone, Debug, Default)]
+pub struct CompileOptions {
+    /// Allow the escapes \n, \t, \\ and \" in string constants. Hack has no
+    /// tab, so \t is a space.
+    pub string_escapes: bool,
+    /// Report the implicit conversions between ints, chars and objects that
+    /// Jack allows as errors instead of warnings
+    pub strict_types: bool,
+    /// The sources are every class of the program, so classes that are
+    /// neither among them nor in the OS are reported as undefined. Without
+    /// it they are assumed to be compiled separately.
+    pub whole_program: bool,
+}
+
+/// The errors found while compiling, kept together with the sources so
+/// they can be shown with snippets
+#[derive(Debug)]
+pub struct Diagnostics {
+    pub errors: Vec<CompileError>,
+    // Warnings from every class, including those that compiled
+    pub warnings: Vec<CompileError>,
+    // The name and text of every source
+    sources: Vec<(String, String)>,
+}
+
+impl Diagnostics {
+    /// Render every error and warning in the same way as the command line
+    /// compiler
+    pub fn render(&self, color: bool) -> String {
+        let mut emitter = Emitter::new(color);
+        for (name, source) in &self.sources {
+            emitter.add_source(name, source);
+        }
+        self.errors.iter().chain(&self.warnings).map(|error| emitter.render(error) + "\n").collect()
+    }
+
+    /// Every error and warning as a line of JSON, as printed by
+    /// --error-format=json
+    pub fn to_json(&self) -> String {
+        self.errors.iter().chain(&self.warnings).map(|error| diagnostic::to_json(error) + "\n").collect()
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for error in self.errors.iter().chain(&self.warnings) {
+            writeln!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for Diagnostics {}
+
+/// Compile the source of a single class to VM code without touching the
+/// file system. `name` is the file name used in diagnostics.
+pub fn compile_source(name: &str, source: &str) -> Result<VmOutput, Diagnostics> {
+    compile_source_with(name, source, &CompileOptions::default())
+}
+
+/// Compile the source of a single class like compile_source, with the
+/// given options
+pub fn compile_source_with(name: &str, source: &str, options: &CompileOptions)
+                           -> Result<VmOutput, Diagnostics> {
+    compile_project(&[(name, source)], options).map(|mut outputs| outputs.remove(0))
+}
+
+/// Compile the classes of a project, which can call each other. Each source
+/// is given as its file name and text, and the output is in the same order.
+pub fn compile_project(sources: &[(&str, &str)], options: &CompileOptions)
+                       -> Result<Vec<VmOutput>, Diagnostics> {
+    let mut outputs = Vec::new();
+    let mut errors = Vec::new();
+    let mut all_warnings = Vec::new();
+    for result in compile_sources(sources, options, Emit::Vm) {
+        let (source_errors, warnings): (Vec<_>, Vec<_>) =
+            result.diagnostics.into_iter().partition(|error| error.is_error());
+        errors.extend(source_errors);
+        all_warnings.extend(warnings.iter().cloned());
+        if let (Some(class_name), Some(code)) = (result.class_name, result.output) {
+            outputs.push(VmOutput { class_name, code, warnings });
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(outputs)
+    } else {
+        Err(Diagnostics {
+            errors,
+            warnings: all_warnings,
+            sources: sources.iter().map(|&(name, source)| (name.to_string(), source.to_string())).collect(),
+        })
+    }
+}
+
+// Run one of the writers of xml_output on a file in memory and return what
+// it wrote
+fn write_xml<F>(name: &str, write: F) -> Result<String, Vec<CompileError>>
+    where F: FnOnce(&mut dyn OutputSink, &Path) -> Result<(), Vec<CompileError>>
+{
+    let mut sink = MemorySink::new();
+    write(&mut sink, Path::new(name))?;
+    // The XML is made of the text of the source, which is valid UTF-8
+    Ok(String::from_utf8(sink.get(Path::new(name)).unwrap_or_default()).unwrap())
+}
+
+/// Compile the sources of a project to VM code or XML. Each source is given
+/// as its file name and text, and the results are in the same order. The
+/// output of a source is only kept when there were no errors in it, so a
+/// failed compile never looks like a complete program.
+pub fn compile_sources(sources: &[(&str, &str)], options: &CompileOptions, emit: Emit) -> Vec<SourceOutput> {
+    let lex_options = LexOptions { string_escapes: options.string_escapes };
+    if emit == Emit::TokensXml {
+        return sources.iter().map(|&(name, source)| {
+            let analyzer = JackAnalyzer::from_source_with(name, source, lex_options);
+            match write_xml(name, |sink, path| xml_output::write_tokens(analyzer, sink, path)) {
+                Ok(xml) => SourceOutput { class_name: None, output: Some(xml), diagnostics: Vec::new() },
+                Err(errors) => SourceOutput { class_name: None, output: None, diagnostics: errors },
+            }
+        }).collect();
+    }
+
+    // Parse every source and collect the classes in an index first, so that
+    // calls from one class to another can be checked. Classes of the OS are
+    // replaced by those of the same name in the sources.
+    let mut parsed = Vec::new();
+    let mut class_index = ClassIndex::with_os();
+    let mut all_parsed = true;
+    for &(name, source) in sources {
+        let analyzer = JackAnalyzer::from_source_with(name, source, lex_options);
+        parsed.push(match Parser::new(analyzer).parse_class() {
+            Ok(class) => match class_index.add_class(&class) {
+                Ok(()) => (Some(class), Vec::new()),
+                Err(errors) => (None, errors),
+            },
+            Err(errors) => {
+                all_parsed = false;
+                (None, errors)
+            }
+        });
+    }
+    // A class that could not be parsed is missing from the index, and uses
+    // of it should not be reported too
+    class_index.set_complete(options.whole_program && all_parsed);
+
+    let class_index = Rc::new(class_index);
+    parsed.into_iter().zip(sources).map(|((class, mut diagnostics), &(name, _))| {
+        let class = match class {
+            Some(class) => class,
+            None => return SourceOutput { class_name: None, output: None, diagnostics },
+        };
+
+        let output = match emit {
+            Emit::Xml | Emit::AnnotatedXml => {
+                let annotated = emit == Emit::AnnotatedXml;
+                write_xml(name, |sink, path| xml_output::write_parse_tree(&class, sink, path, annotated))
+            }
+            _ => {
+                diagnostics.extend(TypeChecker::new(&class_index, options.strict_types).check_class(&class));
+                let mut compiler = CompilationEngine::from_writer(name, Vec::new())
+                    .with_class_index(class_index.clone());
+                // The VM writer only writes valid UTF-8
+                compiler.compile_class(&class).map(|()| String::from_utf8(compiler.into_inner()).unwrap())
+            }
+        };
+        let output = match output {
+            Ok(output) => Some(output),
+            Err(errors) => {
+                diagnostics.extend(errors);
+                None
+            }
+        };
+        SourceOutput {
+            class_name: Some(class.name.name),
+            output: output.filter(|_| !diagnostics.iter().any(|error| error.is_error())),
+            diagnostics,
+        }
+    }).collect()
+}
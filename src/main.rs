@@ -1,31 +1,257 @@
-mod jack_analyzer;
-mod compilation_engine;
-mod xml_output;
-mod symbol_table;
-mod vm_writer;
+extern crate jackcompiler;
 
-use compilation_engine::*;
+use jackcompiler::diagnostic;
+use jackcompiler::diagnostic::Emitter;
+use jackcompiler::error::CompileError;
+use jackcompiler::vfs::{FileSystem, OutputSink, SourceProvider, ZipSource};
+use jackcompiler::{CompileOptions, Emit};
 
 use std::env;
-use std::path::Path;
+use std::io::prelude::*;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process;
+
+#[derive(Clone, Copy, PartialEq)]
+enum ErrorFormat {
+    Human,
+    Json,
+}
+
+fn report(error: &CompileError, emitter: &Emitter, format: ErrorFormat) {
+    match format {
+        ErrorFormat::Human => eprintln!("{}", emitter.render(error)),
+        ErrorFormat::Json => eprintln!("{}", diagnostic::to_json(error)),
+    }
+}
+
+// Print the errors and warnings from a file
+fn report_all(diagnostics: &[CompileError], emitter: &Emitter, format: ErrorFormat) {
+    for error in diagnostics {
+        report(error, emitter, format);
+    }
+}
+
+// Write the output of a file and print the error if it cannot be written,
+// returns false if it failed
+fn write_output(sink: &mut dyn OutputSink, outfile: &Path, output: &str, emitter: &Emitter, format: ErrorFormat)
+                -> bool {
+    let written = sink.create(outfile).and_then(|mut out| {
+        out.write_all(output.as_bytes())?;
+        out.flush()
+    });
+    match written {
+        Ok(()) => true,
+        Err(why) => {
+            report(&CompileError::io(&outfile.display().to_string(), &why), emitter, format);
+            false
+        }
+    }
+}
+
+const USAGE: &str = "usage: jackcompiler [options] files, directories or zip archives
+
+zip archives are compiled like directories without unpacking them, and the
+output goes to a directory with the name of the archive unless --out-dir is
+given. When only directories and archives are given they are taken to hold
+the whole program, and classes that are neither in them nor in the OS are
+reported as undefined.
+
+options:
+    -r, --recursive         also compile .jack files in subdirectories
+    -o, --out-dir DIR       write the output files to DIR
+    --emit KIND             what to write for each file:
+                              vm          VM code in Xxx.vm (the default)
+                              tokens-xml  the tokens in XxxT.xml
+                              xml         the parse tree in Xxx.xml
+                              annotated-xml
+                                          the parse tree in Xxx.xml with the
+                                          symbol table entry of each identifier
+    --string-escapes        allow \\n, \\t, \\\\ and \\\" in string constants.
+                            Hack has no tab, so \\t is a space
+    --strict-types          report the implicit conversions between int, char
+                            and objects that Jack allows as errors instead
+                            of warnings
+    --color=WHEN            color diagnostics: auto, always or never
+    --error-format=FORMAT   print diagnostics as human or json. With json
+                            nothing else is printed, so every line of
+                            output is a diagnostic";
+
+struct Options {
+    inputs: Vec<String>,
+    recursive: bool,
+    out_dir: Option<PathBuf>,
+    emit: Emit,
+    compile: CompileOptions,
+    color: bool,
+    format: ErrorFormat,
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        inputs: Vec::new(),
+        recursive: false,
+        out_dir: None,
+        emit: Emit::Vm,
+        compile: CompileOptions::default(),
+        color: std::io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none(),
+        format: ErrorFormat::Human,
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-r" | "--recursive" => options.recursive = true,
+            "-o" | "--out-dir" => match args.next() {
+                Some(dir) => options.out_dir = Some(PathBuf::from(dir)),
+                None => usage_error(&format!("{} needs a directory", arg)),
+            },
+            "--emit" => match args.next().as_ref().and_then(|kind| Emit::parse(kind)) {
+                Some(emit) => options.emit = emit,
+                None => usage_error("--emit needs one of vm, tokens-xml, xml or annotated-xml"),
+            },
+            "--string-escapes" => options.compile.string_escapes = true,
+            "--strict-types" => options.compile.strict_types = true,
+            "--color=always" => options.color = true,
+            "--color=never" => options.color = false,
+            "--color=auto" => (),
+            "--error-format=human" => options.format = ErrorFormat::Human,
+            "--error-format=json" => options.format = ErrorFormat::Json,
+            _ if arg.starts_with("--out-dir=") => {
+                options.out_dir = Some(PathBuf::from(&arg["--out-dir=".len()..]));
+            }
+            _ if arg.starts_with("--emit=") => match Emit::parse(&arg["--emit=".len()..]) {
+                Some(emit) => options.emit = emit,
+                None => usage_error(&format!("unknown output kind in {}", arg)),
+            },
+            _ if arg.starts_with('-') => usage_error(&format!("unknown option {}", arg)),
+            _ => options.inputs.push(arg),
+        }
+    }
+
+    if options.inputs.is_empty() {
+        usage_error("no input files");
+    }
+    options
+}
+
+// Where the output for a file should go. `base` is the directory given on
+// the command line, so subdirectories are kept in the output.
+fn output_path(file: &Path, base: Option<&Path>, out_dir: Option<&PathBuf>, emit: Emit) -> PathBuf {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let out_file = file.with_file_name(format!("{}{}", stem, emit.suffix()));
+    match out_dir {
+        Some(out_dir) => {
+            let relative = match base {
+                Some(base) => out_file.strip_prefix(base).unwrap_or(&out_file).to_path_buf(),
+                None => PathBuf::from(out_file.file_name().unwrap()),
+            };
+            out_dir.join(relative)
+        }
+        None => out_file,
+    }
+}
 
 fn main() {
-    let args: Vec<_> = env::args().collect();
-    let mut current_file: usize = 1;
-    if args.len() < 2 {
-        println!("usage: jackcompiler files");
-    } else {
-        // Compile every file
-        while current_file < args.len() {
-            let filename = &args[current_file];
-            let path = Path::new(filename);
-            let outfile = path.with_extension("vm");
-            
-            println!("Compiling {} to {}", filename, outfile.display());
-            
-            let mut compiler = CompilationEngine::new(path, &outfile);
-            compiler.compile_class();
-            current_file += 1;
+    let options = parse_args();
+    let mut emitter = Emitter::new(options.color);
+    let mut compiled = 0;
+    let mut failed = 0;
+
+    // Where the inputs are read from. Zip archives are read into memory and
+    // compiled like directories.
+    let mut sources: Vec<Box<dyn SourceProvider>> = vec![Box::new(FileSystem)];
+    let mut sink = FileSystem;
+
+    // Find every file to compile, which source it is in and where it should be written
+    let mut jobs = Vec::new();
+    let mut whole_program = true;
+    for input in &options.inputs {
+        let path = Path::new(input);
+        let mut out_dir = options.out_dir.clone();
+        let source = if path.extension().is_some_and(|ext| ext == "zip") {
+            match ZipSource::open(path) {
+                Ok(archive) => {
+                    // Write the output next to the archive, in a directory with the same name
+                    out_dir = out_dir.or_else(|| Some(path.with_extension("")));
+                    sources.push(Box::new(archive));
+                    sources.len() - 1
+                }
+                Err(why) => {
+                    report(&CompileError::io(input, &why), &emitter, options.format);
+                    failed += 1;
+                    continue;
+                }
+            }
+        } else {
+            0
+        };
+
+        if sources[source].is_dir(path) {
+            match sources[source].jack_files(path, options.recursive) {
+                Ok(files) => for file in files {
+                    let outfile = output_path(&file, Some(path), out_dir.as_ref(), options.emit);
+                    jobs.push((source, file, outfile));
+                },
+                Err(why) => {
+                    report(&CompileError::io(input, &why), &emitter, options.format);
+                    failed += 1;
+                }
+            }
+        } else {
+            let outfile = output_path(path, None, out_dir.as_ref(), options.emit);
+            jobs.push((source, path.to_path_buf(), outfile));
+            whole_program = false;
         }
     }
+
+    // Read every file, keeping its text for the snippets in diagnostics
+    let mut inputs = Vec::new();
+    for (source, path, outfile) in jobs {
+        if options.format == ErrorFormat::Human {
+            println!("Compiling {} to {}", path.display(), outfile.display());
+        }
+
+        let name = path.display().to_string();
+        match sources[source].read(&path) {
+            Ok(text) => {
+                emitter.add_source(&name, &text);
+                inputs.push((name, text, outfile));
+            }
+            Err(why) => {
+                report(&CompileError::io(&name, &why), &emitter, options.format);
+                failed += 1;
+            }
+        }
+    }
+
+    // Compile them together, so calls from one class to another are checked,
+    // and write the output of those without errors
+    let texts: Vec<(&str, &str)> = inputs.iter().map(|(name, text, _)| (&**name, &**text)).collect();
+    let compile_options = CompileOptions { whole_program, ..options.compile.clone() };
+    let results = jackcompiler::compile_sources(&texts, &compile_options, options.emit);
+    for (result, (_, _, outfile)) in results.iter().zip(&inputs) {
+        report_all(&result.diagnostics, &emitter, options.format);
+        let written = match result.output {
+            Some(ref output) => write_output(&mut sink, outfile, output, &emitter, options.format),
+            None => false,
+        };
+        if written {
+            compiled += 1;
+        } else {
+            failed += 1;
+        }
+    }
+
+    if options.format == ErrorFormat::Human {
+        println!("{} compiled, {} failed", compiled, failed);
+    }
+    if failed > 0 {
+        process::exit(1);
+    }
 }
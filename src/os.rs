@@ -0,0 +1,96 @@
+// The standard library of the Jack OS. Programs call it all the time but it
+// is not part of the project, so its classes are described here in Jack
+// itself, with empty bodies, and read into the class index.
+
+use class_index::{ClassIndex, ClassInfo};
+use jack_analyzer::JackAnalyzer;
+use parser::Parser;
+
+/// The declarations of every OS class, as given in the nand2tetris book
+pub const OS_CLASSES: &[&str] = &[
+    "class Math {
+        function void init() {}
+        function int abs(int x) {}
+        function int multiply(int x, int y) {}
+        function int divide(int x, int y) {}
+        function int min(int x, int y) {}
+        function int max(int x, int y) {}
+        function int sqrt(int x) {}
+    }",
+    "class String {
+        constructor String new(int maxLength) {}
+        method void dispose() {}
+        method int length() {}
+        method char charAt(int j) {}
+        method void setCharAt(int j, char c) {}
+        method String appendChar(char c) {}
+        method void eraseLastChar() {}
+        method int intValue() {}
+        method void setInt(int val) {}
+        function char backSpace() {}
+        function char doubleQuote() {}
+        function char newLine() {}
+    }",
+    "class Array {
+        function Array new(int size) {}
+        method void dispose() {}
+    }",
+    "class Output {
+        function void init() {}
+        function void moveCursor(int i, int j) {}
+        function void printChar(char c) {}
+        function void printString(String s) {}
+        function void printInt(int i) {}
+        function void println() {}
+        function void backSpace() {}
+    }",
+    "class Screen {
+        function void init() {}
+        function void clearScreen() {}
+        function void setColor(boolean b) {}
+        function void drawPixel(int x, int y) {}
+        function void drawLine(int x1, int y1, int x2, int y2) {}
+        function void drawRectangle(int x1, int y1, int x2, int y2) {}
+        function void drawCircle(int x, int y, int r) {}
+    }",
+    "class Keyboard {
+        function void init() {}
+        function char keyPressed() {}
+        function char readChar() {}
+        function String readLine(String message) {}
+        function int readInt(String message) {}
+    }",
+    "class Memory {
+        function void init() {}
+        function int peek(int address) {}
+        function void poke(int address, int value) {}
+        function Array alloc(int size) {}
+        function void deAlloc(Array o) {}
+    }",
+    "class Sys {
+        function void init() {}
+        function void halt() {}
+        function void error(int errorCode) {}
+        function void wait(int duration) {}
+    }",
+];
+
+/// The classes of the OS. They have no spans since they are not in any
+/// source of the project.
+pub fn classes() -> Vec<ClassInfo> {
+    let mut index = ClassIndex::new();
+    for source in OS_CLASSES {
+        let class = Parser::new(JackAnalyzer::from_source("OS.jack", source)).parse_class()
+            .expect("the OS declarations should parse");
+        index.add_class(&class).expect("the OS declarations should not define anything twice");
+    }
+
+    let mut classes: Vec<ClassInfo> = index.classes().cloned().collect();
+    for class in &mut classes {
+        class.span = None;
+        for subroutine in &mut class.subroutines {
+            subroutine.span = None;
+        }
+    }
+    classes
+}
@@ -0,0 +1,610 @@
+use ast::*;
+use error::*;
+use jack_analyzer::*;
+use span::Span;
+use xml_output::keyword_to_str;
+use xml_output::make_tag_string;
+
+/// Builds the syntax tree of a class from the tokens of a JackAnalyzer.
+/// Errors inside a statement or subroutine are recorded and parsing
+/// continues after them, so one run reports as many as possible.
+pub struct Parser {
+    analyzer: JackAnalyzer,
+    // The last token that was skipped, where the node being parsed ends
+    prev_span: Span,
+    errors: Vec<CompileError>,
+}
+
+impl Parser {
+    pub fn new(analyzer: JackAnalyzer) -> Parser {
+        let prev_span = analyzer.span();
+        Parser {
+            analyzer,
+            prev_span,
+            errors: Vec::new(),
+        }
+    }
+
+    fn advance(&mut self) -> CompileResult<()> {
+        self.prev_span = self.analyzer.span();
+        self.analyzer.advance()
+    }
+
+    // The span from `start` to the end of the last skipped token
+    fn span_from(&self, start: &Span) -> Span {
+        start.to(&self.prev_span)
+    }
+
+    fn syntax_error<T, S: Into<String>>(&self, message: S) -> CompileResult<T> {
+        Err(CompileError::syntax(self.analyzer.span(), message))
+    }
+
+    fn at_symbol(&self, sym: char) -> bool {
+        self.analyzer.peek(0).is_some_and(|token| token.kind == TokenType::Symbol && token.symbol() == sym)
+    }
+
+    fn at_keyword(&self, keyword: Keyword) -> bool {
+        self.at_any_keyword(&[keyword])
+    }
+
+    fn at_any_keyword(&self, keywords: &[Keyword]) -> bool {
+        self.analyzer.peek(0).and_then(Token::keyword).is_some_and(|keyword| keywords.contains(&keyword))
+    }
+
+    // Check that the current token is the expected symbol and skip it
+    fn expect_symbol(&mut self, sym: char, message: &str) -> CompileResult<()> {
+        if !self.at_symbol(sym) {
+            return self.syntax_error(message);
+        }
+        self.advance()
+    }
+
+    // Check for the symbol closing a pair opened at `opening` and skip it
+    fn expect_closing(&mut self, sym: char, opening: &Span, message: &str) -> CompileResult<()> {
+        if !self.at_symbol(sym) {
+            let open_sym = match sym {
+                ')' => '(',
+                ']' => '[',
+                _ => '{',
+            };
+            return Err(CompileError::syntax(self.analyzer.span(), message)
+                       .with_note(Some(opening.clone()), format!("unclosed {} is here", open_sym)));
+        }
+        self.advance()
+    }
+
+    // Read an identifier and skip it
+    fn expect_identifier(&mut self, message: &str) -> CompileResult<Ident> {
+        if self.analyzer.token_type() != TokenType::Identifier {
+            return self.syntax_error(message);
+        }
+        let token = self.analyzer.token();
+        let ident = Ident {
+            name: token.text.clone(),
+            span: token.span.clone(),
+        };
+        self.advance()?;
+        Ok(ident)
+    }
+
+    // Read a type (int, char, boolean or a class name) and skip it
+    fn parse_type(&mut self) -> CompileResult<Type> {
+        let token = self.analyzer.token();
+        let var_type = match (token.kind, token.keyword()) {
+            (TokenType::Identifier, _) => Type::Class(Ident {
+                name: token.text.clone(),
+                span: token.span.clone(),
+            }),
+            (_, Some(Keyword::Int)) => Type::Int,
+            (_, Some(Keyword::Char)) => Type::Char,
+            (_, Some(Keyword::Boolean)) => Type::Boolean,
+            _ => return self.syntax_error(format!("Expected a type, found {}",
+                                                  make_tag_string(&self.analyzer).trim())),
+        };
+        self.advance()?;
+        Ok(var_type)
+    }
+
+    // Record an error from skipping a token. Running into the end of the
+    // file is left to the caller, but an unterminated comment there is kept.
+    fn push_skip_error(&mut self, error: CompileError) {
+        if error.code != E_UNEXPECTED_EOF {
+            self.errors.push(error);
+        }
+    }
+
+    // Skip tokens until a point where parsing can continue after an error.
+    // Stops before any of the keywords or a closing brace, after a semicolon
+    // or after a complete block. Returns false at the end of the file.
+    fn synchronize(&mut self, keywords: &[Keyword]) -> bool {
+        let mut depth = 0;
+        loop {
+            if self.analyzer.at_eof() {
+                return false;
+            }
+            if depth == 0 {
+                if self.at_symbol('}') {
+                    return true;
+                }
+                if self.at_any_keyword(keywords) {
+                    return true;
+                }
+            }
+
+            let ends_here = if self.at_symbol('{') {
+                depth += 1;
+                false
+            } else if self.at_symbol('}') {
+                depth -= 1;
+                depth == 0
+            } else {
+                depth == 0 && self.at_symbol(';')
+            };
+
+            if let Err(error) = self.advance() {
+                self.push_skip_error(error);
+                if self.analyzer.at_eof() {
+                    return false;
+                }
+            }
+            if ends_here {
+                return !self.analyzer.at_eof();
+            }
+        }
+    }
+
+    // Skip tokens until the start of the next subroutine or class variable
+    // declaration, which can be the token the error was found at. Returns
+    // false at the end of the file.
+    fn skip_to_member(&mut self) -> bool {
+        let member_keywords = [Keyword::Constructor, Keyword::Function, Keyword::Method,
+                               Keyword::Static, Keyword::Field];
+        loop {
+            if self.at_any_keyword(&member_keywords) {
+                return true;
+            }
+            if let Err(error) = self.advance() {
+                self.push_skip_error(error);
+                if self.analyzer.at_eof() {
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// Parse the whole file, returning every error that was found
+    pub fn parse_class(&mut self) -> Result<Class, Vec<CompileError>> {
+        match self.parse_class_body() {
+            Ok(class) if self.errors.is_empty() => Ok(class),
+            Ok(_) => Err(self.errors.drain(..).collect()),
+            Err(error) => {
+                self.errors.push(error);
+                Err(self.errors.drain(..).collect())
+            }
+        }
+    }
+
+    fn parse_class_body(&mut self) -> CompileResult<Class> {
+        self.analyzer.advance()?;
+        if !self.at_keyword(Keyword::Class) {
+            return Err(CompileError::syntax(self.analyzer.span(), "File must start with class")
+                       .with_help("every Jack file contains a single class declaration"));
+        }
+        let start = self.analyzer.span();
+
+        self.advance()?;
+        let name = self.expect_identifier("No class name")?;
+
+        self.expect_symbol('{', "Missing opening brace")?;
+
+        let mut var_decs = Vec::new();
+        let mut subroutines = Vec::new();
+        while !self.at_symbol('}') {
+            let result = match self.analyzer.key_word() {
+                Some(Keyword::Static) | Some(Keyword::Field) =>
+                    self.parse_class_var_dec().map(|var_dec| var_decs.push(var_dec)),
+                Some(Keyword::Constructor) | Some(Keyword::Function) | Some(Keyword::Method) =>
+                    self.parse_subroutine().map(|subroutine| subroutines.push(subroutine)),
+                _ => self.syntax_error(format!("Unknown token inside class: {}",
+                                               make_tag_string(&self.analyzer).trim())),
+            };
+
+            if let Err(error) = result {
+                self.errors.push(error);
+                // Continue with the next subroutine or declaration
+                if !self.skip_to_member() {
+                    break;
+                }
+            }
+        }
+
+        let span = start.to(&self.analyzer.span());
+
+        // The closing brace must be the last token
+        match self.advance() {
+            Ok(()) => {
+                let error = CompileError::syntax(self.analyzer.span(),
+                                                 format!("Unexpected tokens after the end of class {}", name.name))
+                    .with_help("every Jack file contains a single class declaration");
+                self.errors.push(error);
+            }
+            Err(error) => self.push_skip_error(error),
+        }
+        Ok(Class { name, var_decs, subroutines, span })
+    }
+
+    // Parse the type and names of a variable declaration, after the keyword
+    fn parse_var_names(&mut self) -> CompileResult<(Type, Vec<Ident>)> {
+        let var_type = self.parse_type()?;
+
+        let mut names = Vec::new();
+        loop {
+            names.push(self.expect_identifier("Expected variable name")?);
+
+            // Skip commas
+            if !self.at_symbol(',') {
+                break;
+            }
+            self.advance()?;
+        }
+
+        // Skip semicolon
+        self.expect_symbol(';', "Expected ; at end of variable declaration")?;
+        Ok((var_type, names))
+    }
+
+    pub fn parse_class_var_dec(&mut self) -> CompileResult<ClassVarDec> {
+        let start = self.analyzer.span();
+        let kind = if self.at_keyword(Keyword::Static) {
+            ClassVarKind::Static
+        } else {
+            ClassVarKind::Field
+        };
+        self.advance()?;
+
+        let (var_type, names) = self.parse_var_names()?;
+        Ok(ClassVarDec { kind, var_type, names, span: self.span_from(&start) })
+    }
+
+    pub fn parse_subroutine(&mut self) -> CompileResult<SubroutineDec> {
+        let start = self.analyzer.span();
+        let kind = match self.analyzer.key_word() {
+            Some(Keyword::Constructor) => SubroutineKind::Constructor,
+            Some(Keyword::Method) => SubroutineKind::Method,
+            _ => SubroutineKind::Function,
+        };
+        self.advance()?;
+
+        let return_type = if self.at_keyword(Keyword::Void) {
+            self.advance()?;
+            None
+        } else {
+            Some(self.parse_type()?)
+        };
+
+        let name = self.expect_identifier("No function name")?;
+
+        let open_span = self.analyzer.span();
+        self.expect_symbol('(', "Missing parameter list")?;
+        let parameters = self.parse_parameter_list()?;
+        self.expect_closing(')', &open_span, "Missing closing parenthesis")?;
+
+        self.expect_symbol('{', "Missing function opening brace")?;
+
+        // Parse local variable declarations
+        let mut locals = Vec::new();
+        while self.at_keyword(Keyword::Var) {
+            locals.push(self.parse_var_dec()?);
+        }
+
+        let body = self.parse_statements()?;
+
+        // Skip closing brace
+        self.advance()?;
+        Ok(SubroutineDec {
+            kind,
+            return_type,
+            name,
+            parameters,
+            locals,
+            body,
+            span: self.span_from(&start),
+        })
+    }
+
+    pub fn parse_parameter_list(&mut self) -> CompileResult<Vec<Parameter>> {
+        let mut parameters = Vec::new();
+        while !self.at_symbol(')') {
+            let var_type = self.parse_type()?;
+            let name = self.expect_identifier("Expected parameter name")?;
+            parameters.push(Parameter { var_type, name });
+
+            // Skip commas between arguments
+            if self.at_symbol(',') {
+                self.advance()?;
+            } else if !self.at_symbol(')') {
+                return self.syntax_error("Expected , or ) after parameter");
+            }
+        }
+        Ok(parameters)
+    }
+
+    pub fn parse_var_dec(&mut self) -> CompileResult<VarDec> {
+        let start = self.analyzer.span();
+        // Skip var keyword
+        self.advance()?;
+
+        let (var_type, names) = self.parse_var_names()?;
+        Ok(VarDec { var_type, names, span: self.span_from(&start) })
+    }
+
+    pub fn parse_statements(&mut self) -> CompileResult<Vec<Statement>> {
+        let statement_keywords = [Keyword::Let, Keyword::If, Keyword::While, Keyword::Do, Keyword::Return];
+
+        let mut statements = Vec::new();
+        while !self.at_symbol('}') {
+            let result = match self.analyzer.key_word() {
+                Some(Keyword::Let) => self.parse_let(),
+                Some(Keyword::If) => self.parse_if(),
+                Some(Keyword::While) => self.parse_while(),
+                Some(Keyword::Do) => self.parse_do(),
+                Some(Keyword::Return) => self.parse_return(),
+                Some(other) => self.syntax_error(format!("Invalid keyword at start of statement: {}",
+                                                         keyword_to_str(&other))),
+                None => Err(CompileError::syntax(self.analyzer.span(), "Statement must begin with keyword")
+                            .with_help("statements start with let, if, while, do or return")),
+            };
+
+            match result {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    if self.analyzer.at_eof() {
+                        return Err(error);
+                    }
+                    // Report the error and continue with the next statement
+                    self.errors.push(error);
+                    if !self.synchronize(&statement_keywords) {
+                        return Err(CompileError::syntax(self.analyzer.span(), "Unexpected end of file")
+                                   .with_code(E_UNEXPECTED_EOF));
+                    }
+                }
+            }
+        }
+        Ok(statements)
+    }
+
+    // Parse the rest of a call after its first identifier, which is either
+    // the subroutine name or the class or object it is called on
+    fn parse_subroutine_call(&mut self, name1: Ident) -> CompileResult<SubroutineCall> {
+        let start = name1.span.clone();
+        let (receiver, name) = if self.at_symbol('.') {
+            self.advance()?;
+            let name = self.expect_identifier("Expected function name after .")?;
+            if !self.at_symbol('(') {
+                return self.syntax_error(format!("Expected ( after function name. found {} instead",
+                                                 make_tag_string(&self.analyzer).trim()));
+            }
+            (Some(name1), name)
+        } else if self.at_symbol('(') {
+            (None, name1)
+        } else {
+            return self.syntax_error("Expected one of . and ( after identifier in function call");
+        };
+        // Skip (
+        self.advance()?;
+
+        let args = self.parse_expression_list()?;
+
+        // Skip )
+        if !self.at_symbol(')') {
+            return self.syntax_error(format!("Expected ) after function arguments. found {} instead",
+                                             make_tag_string(&self.analyzer).trim()));
+        }
+        self.advance()?;
+
+        Ok(SubroutineCall { receiver, name, args, span: self.span_from(&start) })
+    }
+
+    pub fn parse_do(&mut self) -> CompileResult<Statement> {
+        let start = self.analyzer.span();
+        // Skip do
+        self.advance()?;
+
+        let name1 = self.expect_identifier("Expected function name in do statement")?;
+        if self.analyzer.token_type() != TokenType::Symbol {
+            return self.syntax_error("Symbol . or ( expected after identifier in do statement");
+        }
+        let call = self.parse_subroutine_call(name1)?;
+
+        self.expect_symbol(';', "Expected ; at end of do statement")?;
+        Ok(Statement { kind: StatementKind::Do(call), span: self.span_from(&start) })
+    }
+
+    pub fn parse_let(&mut self) -> CompileResult<Statement> {
+        let start = self.analyzer.span();
+        // Skip let keyword
+        self.advance()?;
+
+        let target = self.expect_identifier("Expected variable name in let statement")?;
+
+        // Handle array element assignment
+        let index = if self.at_symbol('[') {
+            // Skip [
+            let open_span = self.analyzer.span();
+            self.advance()?;
+
+            let index = self.parse_expression()?;
+            self.expect_closing(']', &open_span, "Expected ] after array index")?;
+            Some(index)
+        } else {
+            None
+        };
+
+        self.expect_symbol('=', "Expected = in let statement")?;
+        let value = self.parse_expression()?;
+
+        self.expect_symbol(';', "Expected ; at end of let statement")?;
+        Ok(Statement { kind: StatementKind::Let { target, index, value }, span: self.span_from(&start) })
+    }
+
+    pub fn parse_while(&mut self) -> CompileResult<Statement> {
+        let start = self.analyzer.span();
+        // Skip while keyword
+        self.advance()?;
+        let open_span = self.analyzer.span();
+        self.expect_symbol('(', "Missing expression for while loop")?;
+
+        let condition = self.parse_expression()?;
+
+        self.expect_closing(')', &open_span, "Missing closing parenthesis for while expression")?;
+        self.expect_symbol('{', "Missing opening brace on while loop")?;
+
+        let body = self.parse_statements()?;
+
+        // Skip }
+        self.advance()?;
+        Ok(Statement { kind: StatementKind::While { condition, body }, span: self.span_from(&start) })
+    }
+
+    pub fn parse_return(&mut self) -> CompileResult<Statement> {
+        let start = self.analyzer.span();
+        // Skip return keyword
+        self.advance()?;
+        let value = if !self.at_symbol(';') {
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+        self.expect_symbol(';', "Expected ; at end of return statement")?;
+        Ok(Statement { kind: StatementKind::Return(value), span: self.span_from(&start) })
+    }
+
+    pub fn parse_if(&mut self) -> CompileResult<Statement> {
+        let start = self.analyzer.span();
+        // Skip if keyword
+        self.advance()?;
+        let open_span = self.analyzer.span();
+        self.expect_symbol('(', "Missing expression for if statement")?;
+
+        let condition = self.parse_expression()?;
+
+        self.expect_closing(')', &open_span, "Missing closing parenthesis for if expression")?;
+        self.expect_symbol('{', "Missing opening brace on if statement")?;
+
+        let then_body = self.parse_statements()?;
+
+        // Skip closing brace
+        self.advance()?;
+
+        let else_body = if self.at_keyword(Keyword::Else) {
+            // Skip else keyword
+            self.advance()?;
+
+            self.expect_symbol('{', "Missing opening brace on else statement")?;
+            let else_body = self.parse_statements()?;
+
+            // Skip closing brace }
+            self.advance()?;
+            Some(else_body)
+        } else {
+            None
+        };
+        Ok(Statement { kind: StatementKind::If { condition, then_body, else_body }, span: self.span_from(&start) })
+    }
+
+    pub fn parse_expression(&mut self) -> CompileResult<Expression> {
+        let start = self.analyzer.span();
+        let first = self.parse_term()?;
+
+        let mut rest = Vec::new();
+        while let Some(op) = self.binary_op() {
+            self.advance()?;
+            rest.push((op, self.parse_term()?));
+        }
+        Ok(Expression { first, rest, span: self.span_from(&start) })
+    }
+
+    // The binary operator at the current token, if there is one
+    fn binary_op(&self) -> Option<BinaryOp> {
+        match self.analyzer.peek(0) {
+            Some(token) if token.kind == TokenType::Symbol => BinaryOp::from_symbol(token.symbol()),
+            _ => None,
+        }
+    }
+
+    pub fn parse_term(&mut self) -> CompileResult<Term> {
+        let token = self.analyzer.token().clone();
+        let start = token.span.clone();
+        let kind = if token.kind == TokenType::IntConst {
+            self.advance()?;
+            TermKind::IntConst(token.int_val())
+        } else if token.kind == TokenType::StringConst {
+            let string = self.analyzer.string_val();
+            self.advance()?;
+            TermKind::StringConst(string)
+        } else if let Some(keyword) = token.keyword() {
+            let keyword = match keyword {
+                Keyword::True => KeywordConst::True,
+                Keyword::False => KeywordConst::False,
+                Keyword::Null => KeywordConst::Null,
+                Keyword::This => KeywordConst::This,
+                _ => return self.syntax_error("Invalid keyword in expression"),
+            };
+            self.advance()?;
+            TermKind::KeywordConst(keyword)
+        }
+        // Parse negation or inversion
+        else if self.at_symbol('-') || self.at_symbol('~') {
+            let op = if self.at_symbol('-') { UnaryOp::Neg } else { UnaryOp::Not };
+            self.advance()?;
+            TermKind::Unary(op, Box::new(self.parse_term()?))
+        }
+        // Parse sub-expression in ()
+        else if self.at_symbol('(') {
+            // Skip (
+            let open_span = self.analyzer.span();
+            self.advance()?;
+
+            let expression = self.parse_expression()?;
+
+            self.expect_closing(')', &open_span, "Missing closing parenthesis in expression")?;
+            TermKind::Paren(Box::new(expression))
+        } else {
+            // Parse expression that requires variable, function call or array
+            if token.kind != TokenType::Identifier {
+                return self.syntax_error(format!("Unexpected token inside expression term {}",
+                                                 make_tag_string(&self.analyzer).trim()));
+            }
+            let name1 = self.expect_identifier("Expected identifier")?;
+
+            // Check if it's a function call or array
+            if self.at_symbol('(') || self.at_symbol('.') {
+                TermKind::Call(self.parse_subroutine_call(name1)?)
+            } else if self.at_symbol('[') {
+                let open_span = self.analyzer.span();
+                self.advance()?;
+
+                let index = self.parse_expression()?;
+
+                self.expect_closing(']', &open_span, "Expected ] after array index")?;
+                TermKind::Index(name1, Box::new(index))
+            } else {
+                TermKind::Var(name1)
+            }
+        };
+        Ok(Term { kind, span: self.span_from(&start) })
+    }
+
+    pub fn parse_expression_list(&mut self) -> CompileResult<Vec<Expression>> {
+        let mut expressions = Vec::new();
+        while !self.at_symbol(')') {
+            expressions.push(self.parse_expression()?);
+            if self.at_symbol(',') {
+                self.advance()?;
+            } else if !self.at_symbol(')') {
+                return self.syntax_error("Expected , or ) in argument list");
+            }
+        }
+        Ok(expressions)
+    }
+}
@@ -0,0 +1,38 @@
+use std::fmt;
+use std::rc::Rc;
+
+/// A position in a source file. Lines and columns start at 1, columns are
+/// counted in characters and `offset` is the byte offset from the start of
+/// the file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+/// The region of a source file covered by a token or syntax element.
+/// `end` points just past the last character.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub file: Rc<str>,
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Span {
+    pub fn new(file: Rc<str>, start: Location, end: Location) -> Span {
+        Span { file, start, end }
+    }
+
+    /// The span from the start of this one to the end of `other`
+    pub fn to(&self, other: &Span) -> Span {
+        Span::new(self.file.clone(), self.start, other.end)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.start.line, self.start.column)
+    }
+}
@@ -0,0 +1,48 @@
+// Suggestions for names that could not be found, picked from the names
+// that are defined by their edit distance
+
+use error::CompileError;
+
+use std::cmp;
+
+/// The number of characters that have to be inserted, deleted or replaced
+/// to turn one string into the other
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // The distances from the part of a seen so far to each prefix of b
+    let mut previous: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b_char) in b.iter().enumerate() {
+            let replace = previous[j] + if a_char == b_char { 0 } else { 1 };
+            current.push(cmp::min(replace, cmp::min(previous[j + 1], current[j]) + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The candidate closest to a name, if it is close enough to be a likely
+/// typo. Ties go to the candidate that comes first alphabetically.
+pub fn closest<'a, I>(name: &str, candidates: I) -> Option<&'a str>
+    where I: IntoIterator<Item = &'a str>
+{
+    let limit = cmp::max(name.chars().count() / 3, 1);
+    candidates.into_iter()
+        .filter(|&candidate| candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= limit)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Add a help message to an error for a name that was not found with the
+/// closest candidate, if there is one
+pub fn with_suggestion<'a, I>(error: CompileError, name: &str, candidates: I) -> CompileError
+    where I: IntoIterator<Item = &'a str>
+{
+    match closest(name, candidates) {
+        Some(candidate) => error.with_help(format!("did you mean `{}`?", candidate)),
+        None => error,
+    }
+}
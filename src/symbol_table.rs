@@ -1,8 +1,9 @@
-use jack_analyzer::Keyword;
+use ast::ClassVarKind;
+use span::Span;
 
 use std::collections::HashMap;
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Kind {
     None,
     Static,
@@ -11,28 +12,41 @@ pub enum Kind {
     Var,
 }
 
-struct TableEntry {
-    type_name: String,
-    kind: Kind,
-    index: i32,
+/// A variable in the symbol table
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub type_name: String,
+    pub kind: Kind,
+    pub index: i32,
+    // Where the symbol was declared
+    pub span: Option<Span>,
 }
 
+/// The variables of a class and of the subroutine being compiled. Statics
+/// and fields live in the class scope and arguments and locals in the
+/// subroutine scope, which is emptied by start_subroutine. Names in the
+/// subroutine scope hide those in the class scope.
 pub struct SymbolTable {
-    class_symbols: HashMap<String, TableEntry>,
-    function_symbols: HashMap<String, TableEntry>,
-    
+    class_symbols: HashMap<String, Symbol>,
+    function_symbols: HashMap<String, Symbol>,
+
     static_index: i32,
     field_index: i32,
     arg_index: i32,
     var_index: i32,
 }
 
-pub fn keyword_to_kind(keyword: Keyword) -> Kind {
-    match keyword {
-        Keyword::Static => Kind::Static,
-        Keyword::Field => Kind::Field,
-        Keyword::Var => Kind::Var,
-        _ => Kind::None,
+pub fn class_var_kind(kind: ClassVarKind) -> Kind {
+    match kind {
+        ClassVarKind::Static => Kind::Static,
+        ClassVarKind::Field => Kind::Field,
+    }
+}
+
+impl Default for SymbolTable {
+    fn default() -> SymbolTable {
+        SymbolTable::new()
     }
 }
 
@@ -48,35 +62,37 @@ impl SymbolTable {
             var_index: 0,
         }
     }
-    
+
     pub fn start_subroutine(&mut self) {
         self.function_symbols.clear();
         self.arg_index = 0;
         self.var_index = 0;
     }
 
-    pub fn define(&mut self, name: &String, t: &String, k: Kind) {
-        let name_clone = (*name).clone();
-        let t_clone = (*t).clone();
-        match k {
-            Kind::Static => {
-                self.class_symbols.insert(name_clone, TableEntry {type_name: t_clone, kind: k, index: self.static_index});
-                self.static_index += 1;
-            }
-            Kind::Field => {
-                self.class_symbols.insert(name_clone, TableEntry {type_name: t_clone, kind: k, index: self.field_index});
-                self.field_index += 1;
-            }
-            Kind::Arg => {
-                self.function_symbols.insert(name_clone, TableEntry {type_name: t_clone, kind: k, index: self.arg_index});
-                self.arg_index += 1;
-            }
-            Kind::Var => {
-                self.class_symbols.insert(name_clone, TableEntry {type_name: t_clone, kind: k, index: self.var_index});
-                self.var_index += 1;
-            }
-            Kind::None => (),
+    /// Add a variable to the scope its kind belongs to and return its index.
+    /// If the scope already has a variable with the same name, nothing is
+    /// added and the earlier definition is returned instead.
+    pub fn define(&mut self, name: &str, t: &str, k: Kind, span: Option<Span>) -> Result<i32, Symbol> {
+        let (symbols, index) = match k {
+            Kind::Static => (&mut self.class_symbols, &mut self.static_index),
+            Kind::Field => (&mut self.class_symbols, &mut self.field_index),
+            Kind::Arg => (&mut self.function_symbols, &mut self.arg_index),
+            Kind::Var => (&mut self.function_symbols, &mut self.var_index),
+            Kind::None => panic!("Can't define a symbol of kind None!"),
+        };
+
+        if let Some(previous) = symbols.get(name) {
+            return Err(previous.clone());
         }
+        symbols.insert(name.to_string(), Symbol {
+            name: name.to_string(),
+            type_name: t.to_string(),
+            kind: k,
+            index: *index,
+            span,
+        });
+        *index += 1;
+        Ok(*index - 1)
     }
 
     pub fn var_count(&self, kind: Kind) -> i32 {
@@ -89,29 +105,30 @@ impl SymbolTable {
         }
     }
 
-    pub fn kind_of(&self, name: &String) -> Kind {
-        if self.function_symbols.contains_key(name) {
-            self.function_symbols.get(name).unwrap().kind
-        } else if self.class_symbols.contains_key(name) {
-            self.class_symbols.get(name).unwrap().kind
-        } else {
-            Kind::None
-        }
+    /// The variable a name refers to, looking in the subroutine scope first
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.function_symbols.get(name).or_else(|| self.class_symbols.get(name))
     }
 
-    pub fn type_of(&self, name: &String) -> String {
-        if self.function_symbols.contains_key(name) {
-            self.function_symbols.get(name).unwrap().type_name.clone()
-        } else {
-            self.class_symbols.get(name).unwrap().type_name.clone()
-        }
+    pub fn kind_of(&self, name: &str) -> Kind {
+        self.get(name).map_or(Kind::None, |symbol| symbol.kind)
     }
 
-    pub fn index_of(&self, name: &String) -> i32 {
-        if self.function_symbols.contains_key(name) {
-            self.function_symbols.get(name).unwrap().index
-        } else {
-            self.class_symbols.get(name).unwrap().index
-        }
+    pub fn type_of(&self, name: &str) -> Option<String> {
+        self.get(name).map(|symbol| symbol.type_name.clone())
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<i32> {
+        self.get(name).map(|symbol| symbol.index)
+    }
+
+    /// The names of every variable in scope
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.function_symbols.keys().chain(self.class_symbols.keys()).map(|name| &**name)
+    }
+
+    /// Where a symbol was declared
+    pub fn span_of(&self, name: &str) -> Option<Span> {
+        self.get(name).and_then(|symbol| symbol.span.clone())
     }
 }
@@ -0,0 +1,428 @@
+// Checks the types of the expressions in a class against the declarations of
+// its variables and the signatures of the subroutines it calls. Jack is
+// weakly typed: ints and chars and the different kinds of objects convert
+// into each other implicitly. Those conversions are only warnings by
+// default, since the programs of the course rely on them, and errors in
+// strict mode.
+
+use ast::*;
+use class_index::ClassIndex;
+use error::*;
+use span::Span;
+use symbol_table::*;
+
+use std::fmt;
+
+/// The type of a value, as far as the checker can tell
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueType {
+    Int,
+    // An integer constant, which can be used as a char as well
+    IntConst,
+    Char,
+    Boolean,
+    Null,
+    Class(String),
+    // Something whose type is not known, like an undefined variable, which
+    // is reported elsewhere. It is not checked any further.
+    Unknown,
+}
+
+impl ValueType {
+    /// The type with a name as it is written in declarations
+    pub fn from_name(name: &str) -> ValueType {
+        match name {
+            "int" => ValueType::Int,
+            "char" => ValueType::Char,
+            "boolean" => ValueType::Boolean,
+            _ => ValueType::Class(name.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValueType::Int | ValueType::IntConst => write!(f, "int"),
+            ValueType::Char => write!(f, "char"),
+            ValueType::Boolean => write!(f, "boolean"),
+            ValueType::Null => write!(f, "null"),
+            ValueType::Class(ref name) => write!(f, "{}", name),
+            ValueType::Unknown => write!(f, "an unknown type"),
+        }
+    }
+}
+
+// Ordered from the best to the worst
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+enum Conversion {
+    Exact,
+    // Allowed by Jack but probably a mistake
+    Implicit,
+    Invalid,
+}
+
+// How a value of one type can be used where another type is expected
+fn conversion(from: &ValueType, to: &ValueType) -> Conversion {
+    match (from, to) {
+        (ValueType::Unknown, _) | (_, ValueType::Unknown) => Conversion::Exact,
+        _ if from == to => Conversion::Exact,
+        (ValueType::IntConst, ValueType::Int) | (ValueType::IntConst, ValueType::Char) => Conversion::Exact,
+        (ValueType::Null, ValueType::Class(_)) => Conversion::Exact,
+        (ValueType::Int, ValueType::Char) | (ValueType::Char, ValueType::Int) => Conversion::Implicit,
+        // Integers can be used as the address of an object, and arrays
+        // and other objects can be used for each other
+        (ValueType::Int, ValueType::Class(_)) | (ValueType::IntConst, ValueType::Class(_)) |
+        (ValueType::Null, ValueType::Int) => Conversion::Implicit,
+        (ValueType::Class(from), ValueType::Class(to)) if from == "Array" || to == "Array" =>
+            Conversion::Implicit,
+        _ => Conversion::Invalid,
+    }
+}
+
+/// Type checks classes and collects the errors and warnings it finds
+pub struct TypeChecker<'a> {
+    class_index: &'a ClassIndex,
+    // Report implicit conversions as errors instead of warnings
+    strict: bool,
+    symbol_table: SymbolTable,
+    class_name: String,
+    // The full name, return type and declaration of the subroutine being
+    // checked
+    subroutine_name: String,
+    return_type: Option<ValueType>,
+    subroutine_span: Option<Span>,
+    diagnostics: Vec<CompileError>,
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(class_index: &'a ClassIndex, strict: bool) -> TypeChecker<'a> {
+        TypeChecker {
+            class_index,
+            strict,
+            symbol_table: SymbolTable::new(),
+            class_name: String::new(),
+            subroutine_name: String::new(),
+            return_type: None,
+            subroutine_span: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Check a whole class and return the errors and warnings that were
+    /// found. Undefined names are left to the compilation engine.
+    pub fn check_class(&mut self, class: &Class) -> Vec<CompileError> {
+        self.symbol_table = SymbolTable::new();
+        self.class_name = class.name.name.clone();
+
+        // Variables defined twice are reported by the compilation engine
+        for var_dec in &class.var_decs {
+            for name in &var_dec.names {
+                let _ = self.symbol_table.define(&name.name, var_dec.var_type.name(), class_var_kind(var_dec.kind),
+                                                 Some(name.span.clone()));
+            }
+        }
+        for subroutine in &class.subroutines {
+            self.check_subroutine(subroutine);
+        }
+        self.diagnostics.drain(..).collect()
+    }
+
+    fn check_subroutine(&mut self, subroutine: &SubroutineDec) {
+        self.symbol_table.start_subroutine();
+        if subroutine.kind == SubroutineKind::Method {
+            let this_type = self.class_name.clone();
+            let _ = self.symbol_table.define("this", &this_type, Kind::Arg, None);
+        }
+        for parameter in &subroutine.parameters {
+            let _ = self.symbol_table.define(&parameter.name.name, parameter.var_type.name(), Kind::Arg,
+                                             Some(parameter.name.span.clone()));
+        }
+        for var_dec in &subroutine.locals {
+            for name in &var_dec.names {
+                let _ = self.symbol_table.define(&name.name, var_dec.var_type.name(), Kind::Var,
+                                                 Some(name.span.clone()));
+            }
+        }
+
+        self.subroutine_name = format!("{}.{}", self.class_name, subroutine.name.name);
+        // What constructors return is checked by the compilation engine
+        self.return_type = match subroutine.kind {
+            SubroutineKind::Constructor => None,
+            _ => subroutine.return_type.as_ref().map(|t| ValueType::from_name(t.name())),
+        };
+        self.subroutine_span = Some(subroutine.name.span.clone());
+        self.check_statements(&subroutine.body);
+    }
+
+    // Report a value that is used as another type unless the conversion is
+    // exact. Implicit conversions are only warnings unless the checker is
+    // strict.
+    fn check_conversion<F>(&mut self, from: &ValueType, to: &ValueType, error: F)
+        where F: FnOnce() -> CompileError
+    {
+        match conversion(from, to) {
+            Conversion::Exact => (),
+            Conversion::Implicit if self.strict => {
+                let error = error()
+                    .with_help(format!("Jack converts {} to {} implicitly, which is only a warning without \
+                                        strict type checking", from, to));
+                self.diagnostics.push(error);
+            }
+            Conversion::Implicit => {
+                let warning = error().with_severity(Severity::Warning)
+                    .with_help(format!("Jack converts {} to {} implicitly", from, to));
+                self.diagnostics.push(warning);
+            }
+            Conversion::Invalid => self.diagnostics.push(error()),
+        }
+    }
+
+    // The type of a variable, with where it was declared
+    fn variable(&self, name: &Ident) -> (ValueType, Option<Span>) {
+        match self.symbol_table.get(&name.name) {
+            Some(symbol) => (ValueType::from_name(&symbol.type_name), symbol.span.clone()),
+            None => (ValueType::Unknown, None),
+        }
+    }
+
+    // Check that a variable can be indexed and that the index is a number
+    fn check_index(&mut self, name: &Ident, index: &Expression) {
+        let (var_type, declared) = self.variable(name);
+        self.check_conversion(&var_type, &ValueType::Class("Array".to_string()), || {
+            CompileError::semantic(name.span.clone(), format!(
+                "Indexing {}, which has type {}", name.name, var_type))
+                .with_code(E_NOT_AN_ARRAY)
+                .with_note(declared, format!("{} is declared here", name.name))
+        });
+
+        let index_type = self.check_expression(index);
+        self.check_conversion(&index_type, &ValueType::Int, || {
+            CompileError::semantic(index.span.clone(), format!("Array index has type {}, not int", index_type))
+                .with_code(E_TYPE_MISMATCH)
+        });
+    }
+
+    pub fn check_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            match statement.kind {
+                StatementKind::Let { ref target, ref index, ref value } => self.check_let(target, index.as_ref(), value),
+                StatementKind::If { ref condition, ref then_body, ref else_body } => {
+                    self.check_expression(condition);
+                    self.check_statements(then_body);
+                    if let Some(ref else_body) = *else_body {
+                        self.check_statements(else_body);
+                    }
+                }
+                StatementKind::While { ref condition, ref body } => {
+                    self.check_expression(condition);
+                    self.check_statements(body);
+                }
+                StatementKind::Do(ref call) => {
+                    self.check_subroutine_call(call);
+                }
+                StatementKind::Return(ref value) => {
+                    if let Some(ref value) = *value {
+                        self.check_return(value);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_let(&mut self, target: &Ident, index: Option<&Expression>, value: &Expression) {
+        if let Some(index) = index {
+            // Array elements have no type
+            self.check_index(target, index);
+            self.check_expression(value);
+            return;
+        }
+
+        let value_type = self.check_expression(value);
+        let (target_type, declared) = self.variable(target);
+        self.check_conversion(&value_type, &target_type, || {
+            CompileError::semantic(value.span.clone(), format!(
+                "Assigning {} to {}, which has type {}", value_type, target.name, target_type))
+                .with_code(E_TYPE_MISMATCH)
+                .with_note(declared, format!("{} is declared here", target.name))
+        });
+    }
+
+    // Returns without a value, or with a value from a void subroutine, are
+    // reported by the compilation engine
+    fn check_return(&mut self, value: &Expression) {
+        let value_type = self.check_expression(value);
+        if let Some(return_type) = self.return_type.clone() {
+            let name = self.subroutine_name.clone();
+            let declared = self.subroutine_span.clone();
+            self.check_conversion(&value_type, &return_type, || {
+                CompileError::semantic(value.span.clone(), format!(
+                    "Returning {} from {}, which returns {}", value_type, name, return_type))
+                    .with_code(E_TYPE_MISMATCH)
+                    .with_note(declared, format!("{} is declared here", name))
+            });
+        }
+    }
+
+    /// Check a call and its arguments, returning the type of the result or
+    /// None if the subroutine is void
+    pub fn check_subroutine_call(&mut self, call: &SubroutineCall) -> Option<ValueType> {
+        let class_name = match call.receiver {
+            Some(ref receiver) => self.symbol_table.type_of(&receiver.name).unwrap_or_else(|| receiver.name.clone()),
+            None => self.class_name.clone(),
+        };
+        let arg_types: Vec<ValueType> = call.args.iter().map(|arg| self.check_expression(arg)).collect();
+
+        // Calls to subroutines that are not known are reported elsewhere
+        let class_index = self.class_index;
+        let signature = match class_index.subroutine(&class_name, &call.name.name) {
+            Some(signature) => signature,
+            None => return Some(ValueType::Unknown),
+        };
+
+        // Calls with the wrong number of arguments are reported elsewhere
+        if signature.parameters.len() == call.args.len() {
+            let name = format!("{}.{}", class_name, call.name.name);
+            for ((arg, arg_type), parameter) in call.args.iter().zip(&arg_types).zip(&signature.parameters) {
+                let parameter_type = ValueType::from_name(&parameter.type_name);
+                // The OS takes any object where it declares an Array, like
+                // the object to free in Memory.deAlloc
+                let is_os = signature.span.is_none();
+                if is_os && parameter_type == ValueType::Class("Array".to_string()) &&
+                   matches!(*arg_type, ValueType::Class(_)) {
+                    continue;
+                }
+                self.check_conversion(arg_type, &parameter_type, || {
+                    let error = CompileError::semantic(arg.span.clone(), format!(
+                        "Passing {} as {} of {}, which has type {}", arg_type, parameter.name, name,
+                        parameter_type))
+                        .with_code(E_TYPE_MISMATCH);
+                    // Subroutines of the OS are not declared in the project
+                    match signature.span {
+                        Some(ref span) => error.with_note(Some(span.clone()), format!("{} is declared here", name)),
+                        None => error,
+                    }
+                });
+            }
+        }
+
+        signature.return_type.as_ref().map(|return_type| ValueType::from_name(return_type))
+    }
+
+    pub fn check_expression(&mut self, expression: &Expression) -> ValueType {
+        let mut left_type = self.check_term(&expression.first);
+        let mut left_span = expression.first.span.clone();
+        for &(op, ref term) in &expression.rest {
+            let right_type = self.check_term(term);
+            left_type = self.check_operation(op, (&left_type, &left_span), (&right_type, &term.span));
+            left_span = left_span.to(&term.span);
+        }
+        left_type
+    }
+
+    // Check that a value can be used as a number by an operator
+    fn check_number(&mut self, op: char, operand: (&ValueType, &Span)) {
+        let (operand_type, span) = operand;
+        self.check_conversion(operand_type, &ValueType::Int, || {
+            CompileError::semantic(span.clone(), format!("Operand of {} has type {}, not int", op, operand_type))
+                .with_code(E_INVALID_OPERAND)
+        });
+    }
+
+    // Check the operands of a binary operator and return the type of the result
+    fn check_operation(&mut self, op: BinaryOp, left: (&ValueType, &Span), right: (&ValueType, &Span))
+                       -> ValueType {
+        match op {
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => {
+                self.check_number(op.symbol(), left);
+                self.check_number(op.symbol(), right);
+                ValueType::Int
+            }
+            BinaryOp::Lt | BinaryOp::Gt => {
+                self.check_number(op.symbol(), left);
+                self.check_number(op.symbol(), right);
+                ValueType::Boolean
+            }
+            // Logical operators on booleans and bitwise operators on numbers
+            BinaryOp::And | BinaryOp::Or => match (left.0, right.0) {
+                (ValueType::Boolean, ValueType::Boolean) | (ValueType::Boolean, ValueType::Unknown) |
+                (ValueType::Unknown, ValueType::Boolean) => ValueType::Boolean,
+                (ValueType::Unknown, ValueType::Unknown) => ValueType::Unknown,
+                (ValueType::Boolean, _) | (_, ValueType::Boolean) => {
+                    self.diagnostics.push(
+                        CompileError::semantic(left.1.to(right.1), format!(
+                            "Cannot apply {} to {} and {}", op.symbol(), left.0, right.0))
+                            .with_code(E_INVALID_OPERAND)
+                            .with_help(format!("{} works on two booleans or on two numbers", op.symbol())));
+                    ValueType::Unknown
+                }
+                _ => {
+                    self.check_number(op.symbol(), left);
+                    self.check_number(op.symbol(), right);
+                    ValueType::Int
+                }
+            },
+            // Values can be compared if either converts to the other
+            BinaryOp::Eq => {
+                let (from, to) = if conversion(left.0, right.0) < conversion(right.0, left.0) {
+                    (left.0, right.0)
+                } else {
+                    (right.0, left.0)
+                };
+                self.check_conversion(from, to, || {
+                    CompileError::semantic(left.1.to(right.1), format!("Comparing {} with {}", left.0, right.0))
+                        .with_code(E_INVALID_OPERAND)
+                });
+                ValueType::Boolean
+            }
+        }
+    }
+
+    pub fn check_term(&mut self, term: &Term) -> ValueType {
+        match term.kind {
+            TermKind::IntConst(_) => ValueType::IntConst,
+            TermKind::StringConst(_) => ValueType::Class("String".to_string()),
+            TermKind::KeywordConst(KeywordConst::True) | TermKind::KeywordConst(KeywordConst::False) =>
+                ValueType::Boolean,
+            TermKind::KeywordConst(KeywordConst::Null) => ValueType::Null,
+            TermKind::KeywordConst(KeywordConst::This) => ValueType::Class(self.class_name.clone()),
+            TermKind::Var(ref name) => self.variable(name).0,
+            TermKind::Index(ref name, ref index) => {
+                self.check_index(name, index);
+                ValueType::Unknown
+            }
+            TermKind::Call(ref call) => match self.check_subroutine_call(call) {
+                Some(value_type) => value_type,
+                None => {
+                    self.diagnostics.push(
+                        CompileError::semantic(term.span.clone(), format!(
+                            "{} does not return a value", call_name(call)))
+                            .with_code(E_VOID_VALUE)
+                            .with_help("call it with a do statement instead"));
+                    ValueType::Unknown
+                }
+            },
+            TermKind::Paren(ref expression) => self.check_expression(expression),
+            TermKind::Unary(op, ref inner) => {
+                let inner_type = self.check_term(inner);
+                match (op, inner_type) {
+                    (UnaryOp::Neg, ValueType::IntConst) => ValueType::IntConst,
+                    (UnaryOp::Not, ValueType::Boolean) => ValueType::Boolean,
+                    (_, ValueType::Unknown) => ValueType::Unknown,
+                    (_, inner_type) => {
+                        self.check_number(op.symbol(), (&inner_type, &inner.span));
+                        ValueType::Int
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The name of a subroutine as it is written in a call
+fn call_name(call: &SubroutineCall) -> String {
+    match call.receiver {
+        Some(ref receiver) => format!("{}.{}", receiver.name, call.name.name),
+        None => call.name.name.clone(),
+    }
+}
@@ -0,0 +1,252 @@
+// Where the compiler reads its sources from and writes its output to, so the
+// same driver works on the file system, on files held in memory and on zip
+// archives
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// Gives the compiler the Jack files to compile
+pub trait SourceProvider {
+    /// Whether the path is a directory that can be searched for Jack files
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// All .jack files in a directory in alphabetical order, also those in
+    /// subdirectories if `recursive` is set
+    fn jack_files(&self, dir: &Path, recursive: bool) -> io::Result<Vec<PathBuf>>;
+
+    /// The contents of a file
+    fn read(&self, path: &Path) -> io::Result<String>;
+}
+
+/// Takes the files written by the compiler
+pub trait OutputSink {
+    /// Create or truncate a file, creating its directory if needed
+    fn create(&mut self, path: &Path) -> io::Result<Box<dyn Write>>;
+}
+
+fn is_jack_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "jack")
+}
+
+/// The real file system
+pub struct FileSystem;
+
+impl FileSystem {
+    fn find_jack_files(&self, dir: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> io::Result<()> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            entries.push(entry?.path());
+        }
+        entries.sort();
+
+        for path in entries {
+            if path.is_dir() {
+                if recursive {
+                    self.find_jack_files(&path, recursive, files)?;
+                }
+            } else if is_jack_file(&path) {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SourceProvider for FileSystem {
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn jack_files(&self, dir: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        self.find_jack_files(dir, recursive, &mut files)?;
+        Ok(files)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+}
+
+impl OutputSink for FileSystem {
+    fn create(&mut self, path: &Path) -> io::Result<Box<dyn Write>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(Box::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+/// Source files held in memory, by path
+#[derive(Default)]
+pub struct MemorySource {
+    files: BTreeMap<PathBuf, String>,
+}
+
+impl MemorySource {
+    pub fn new() -> MemorySource {
+        MemorySource::default()
+    }
+
+    pub fn add<P: Into<PathBuf>, S: Into<String>>(&mut self, path: P, source: S) {
+        self.files.insert(path.into(), source.into());
+    }
+}
+
+impl SourceProvider for MemorySource {
+    fn is_dir(&self, path: &Path) -> bool {
+        self.files.keys().any(|file| file != path && file.starts_with(path))
+    }
+
+    fn jack_files(&self, dir: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+        // Paths are ordered component by component, which is the same order
+        // as searching the directories one at a time
+        Ok(self.files.keys()
+           .filter(|file| is_jack_file(file) && file.starts_with(dir))
+           .filter(|file| recursive || file.parent() == Some(dir))
+           .cloned()
+           .collect())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<String> {
+        match self.files.get(path) {
+            Some(source) => Ok(source.clone()),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "No such file in memory")),
+        }
+    }
+}
+
+/// The Jack files in a zip archive. They are read into memory when the
+/// archive is opened, as if the archive was a directory containing them.
+pub struct ZipSource {
+    files: MemorySource,
+}
+
+impl ZipSource {
+    pub fn open(path: &Path) -> io::Result<ZipSource> {
+        ZipSource::from_reader(path, File::open(path)?)
+    }
+
+    /// Read an archive from any reader. The files are put under `root`.
+    pub fn from_reader<R: Read + Seek>(root: &Path, reader: R) -> io::Result<ZipSource> {
+        let mut archive = ZipArchive::new(reader)?;
+        let mut files = MemorySource::new();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            // Skip entries with names that would escape the archive
+            let name = match file.enclosed_name() {
+                Some(name) => name.to_path_buf(),
+                None => continue,
+            };
+            if file.is_file() && is_jack_file(&name) {
+                let mut source = String::new();
+                file.read_to_string(&mut source)?;
+                files.add(root.join(name), source);
+            }
+        }
+        Ok(ZipSource { files })
+    }
+}
+
+impl SourceProvider for ZipSource {
+    fn is_dir(&self, path: &Path) -> bool {
+        self.files.is_dir(path)
+    }
+
+    fn jack_files(&self, dir: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+        self.files.jack_files(dir, recursive)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.files.read(path)
+    }
+}
+
+/// Output files collected in memory. Clones share the same files, so one
+/// can be given to the compiler and the other used to read the results.
+#[derive(Clone, Default)]
+pub struct MemorySink {
+    files: Rc<RefCell<BTreeMap<PathBuf, Vec<u8>>>>,
+}
+
+// Appends to a file of a MemorySink
+struct MemoryFile {
+    files: Rc<RefCell<BTreeMap<PathBuf, Vec<u8>>>>,
+    path: PathBuf,
+}
+
+impl Write for MemoryFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut files = self.files.borrow_mut();
+        files.entry(self.path.clone()).or_default().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl MemorySink {
+    pub fn new() -> MemorySink {
+        MemorySink::default()
+    }
+
+    /// The contents of a file that has been written
+    pub fn get(&self, path: &Path) -> Option<Vec<u8>> {
+        self.files.borrow().get(path).cloned()
+    }
+
+    /// The paths of every file that has been written, in alphabetical order
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.files.borrow().keys().cloned().collect()
+    }
+}
+
+impl OutputSink for MemorySink {
+    fn create(&mut self, path: &Path) -> io::Result<Box<dyn Write>> {
+        self.files.borrow_mut().insert(path.to_path_buf(), Vec::new());
+        Ok(Box::new(MemoryFile {
+            files: self.files.clone(),
+            path: path.to_path_buf(),
+        }))
+    }
+}
+
+/// Output files collected into a zip archive, which is written by finish
+#[derive(Default)]
+pub struct ZipSink {
+    files: MemorySink,
+}
+
+impl ZipSink {
+    pub fn new() -> ZipSink {
+        ZipSink::default()
+    }
+
+    /// Write every file to a zip archive and return the writer
+    pub fn finish<W: Write + Seek>(&self, out: W) -> io::Result<W> {
+        let mut archive = ZipWriter::new(out);
+        for path in self.files.paths() {
+            archive.start_file(path.to_string_lossy(), FileOptions::default())?;
+            archive.write_all(&self.files.get(&path).unwrap_or_default())?;
+        }
+        Ok(archive.finish()?)
+    }
+}
+
+impl OutputSink for ZipSink {
+    fn create(&mut self, path: &Path) -> io::Result<Box<dyn Write>> {
+        self.files.create(path)
+    }
+}
@@ -0,0 +1,336 @@
+// Traits for walking the syntax tree. Every visit method defaults to the
+// matching walk function, which visits the children of the node, so a pass
+// only has to override the methods for the nodes it cares about and can call
+// the walk function itself to keep going into the children.
+
+use ast::*;
+
+/// Walks the syntax tree without changing it, e.g. for lints
+pub trait Visitor {
+    fn visit_class(&mut self, class: &Class) {
+        walk_class(self, class);
+    }
+
+    fn visit_class_var_dec(&mut self, var_dec: &ClassVarDec) {
+        walk_class_var_dec(self, var_dec);
+    }
+
+    fn visit_subroutine(&mut self, subroutine: &SubroutineDec) {
+        walk_subroutine(self, subroutine);
+    }
+
+    fn visit_parameter(&mut self, parameter: &Parameter) {
+        walk_parameter(self, parameter);
+    }
+
+    fn visit_var_dec(&mut self, var_dec: &VarDec) {
+        walk_var_dec(self, var_dec);
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression);
+    }
+
+    fn visit_term(&mut self, term: &Term) {
+        walk_term(self, term);
+    }
+
+    fn visit_subroutine_call(&mut self, call: &SubroutineCall) {
+        walk_subroutine_call(self, call);
+    }
+
+    fn visit_type(&mut self, var_type: &Type) {
+        walk_type(self, var_type);
+    }
+
+    // Called for every name in the tree: classes, subroutines and variables
+    fn visit_ident(&mut self, _ident: &Ident) {}
+}
+
+pub fn walk_class<V: Visitor + ?Sized>(visitor: &mut V, class: &Class) {
+    visitor.visit_ident(&class.name);
+    for var_dec in &class.var_decs {
+        visitor.visit_class_var_dec(var_dec);
+    }
+    for subroutine in &class.subroutines {
+        visitor.visit_subroutine(subroutine);
+    }
+}
+
+pub fn walk_class_var_dec<V: Visitor + ?Sized>(visitor: &mut V, var_dec: &ClassVarDec) {
+    visitor.visit_type(&var_dec.var_type);
+    for name in &var_dec.names {
+        visitor.visit_ident(name);
+    }
+}
+
+pub fn walk_subroutine<V: Visitor + ?Sized>(visitor: &mut V, subroutine: &SubroutineDec) {
+    if let Some(ref return_type) = subroutine.return_type {
+        visitor.visit_type(return_type);
+    }
+    visitor.visit_ident(&subroutine.name);
+    for parameter in &subroutine.parameters {
+        visitor.visit_parameter(parameter);
+    }
+    for var_dec in &subroutine.locals {
+        visitor.visit_var_dec(var_dec);
+    }
+    for statement in &subroutine.body {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_parameter<V: Visitor + ?Sized>(visitor: &mut V, parameter: &Parameter) {
+    visitor.visit_type(&parameter.var_type);
+    visitor.visit_ident(&parameter.name);
+}
+
+pub fn walk_var_dec<V: Visitor + ?Sized>(visitor: &mut V, var_dec: &VarDec) {
+    visitor.visit_type(&var_dec.var_type);
+    for name in &var_dec.names {
+        visitor.visit_ident(name);
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match statement.kind {
+        StatementKind::Let { ref target, ref index, ref value } => {
+            visitor.visit_ident(target);
+            if let Some(ref index) = *index {
+                visitor.visit_expression(index);
+            }
+            visitor.visit_expression(value);
+        }
+        StatementKind::If { ref condition, ref then_body, ref else_body } => {
+            visitor.visit_expression(condition);
+            for statement in then_body {
+                visitor.visit_statement(statement);
+            }
+            if let Some(ref else_body) = *else_body {
+                for statement in else_body {
+                    visitor.visit_statement(statement);
+                }
+            }
+        }
+        StatementKind::While { ref condition, ref body } => {
+            visitor.visit_expression(condition);
+            for statement in body {
+                visitor.visit_statement(statement);
+            }
+        }
+        StatementKind::Do(ref call) => visitor.visit_subroutine_call(call),
+        StatementKind::Return(ref value) => {
+            if let Some(ref value) = *value {
+                visitor.visit_expression(value);
+            }
+        }
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &Expression) {
+    visitor.visit_term(&expression.first);
+    for (_, term) in &expression.rest {
+        visitor.visit_term(term);
+    }
+}
+
+pub fn walk_term<V: Visitor + ?Sized>(visitor: &mut V, term: &Term) {
+    match term.kind {
+        TermKind::IntConst(_) | TermKind::StringConst(_) | TermKind::KeywordConst(_) => (),
+        TermKind::Var(ref name) => visitor.visit_ident(name),
+        TermKind::Index(ref name, ref index) => {
+            visitor.visit_ident(name);
+            visitor.visit_expression(index);
+        }
+        TermKind::Call(ref call) => visitor.visit_subroutine_call(call),
+        TermKind::Paren(ref expression) => visitor.visit_expression(expression),
+        TermKind::Unary(_, ref inner) => visitor.visit_term(inner),
+    }
+}
+
+pub fn walk_subroutine_call<V: Visitor + ?Sized>(visitor: &mut V, call: &SubroutineCall) {
+    if let Some(ref receiver) = call.receiver {
+        visitor.visit_ident(receiver);
+    }
+    visitor.visit_ident(&call.name);
+    for arg in &call.args {
+        visitor.visit_expression(arg);
+    }
+}
+
+pub fn walk_type<V: Visitor + ?Sized>(visitor: &mut V, var_type: &Type) {
+    if let Type::Class(ref class) = *var_type {
+        visitor.visit_ident(class);
+    }
+}
+
+/// Walks the syntax tree and can change it in place, e.g. for
+/// instrumentation or optimization passes
+pub trait MutVisitor {
+    fn visit_class_mut(&mut self, class: &mut Class) {
+        walk_class_mut(self, class);
+    }
+
+    fn visit_class_var_dec_mut(&mut self, var_dec: &mut ClassVarDec) {
+        walk_class_var_dec_mut(self, var_dec);
+    }
+
+    fn visit_subroutine_mut(&mut self, subroutine: &mut SubroutineDec) {
+        walk_subroutine_mut(self, subroutine);
+    }
+
+    fn visit_parameter_mut(&mut self, parameter: &mut Parameter) {
+        walk_parameter_mut(self, parameter);
+    }
+
+    fn visit_var_dec_mut(&mut self, var_dec: &mut VarDec) {
+        walk_var_dec_mut(self, var_dec);
+    }
+
+    // Statements are visited as whole blocks so a pass can insert or
+    // remove statements
+    fn visit_statements_mut(&mut self, statements: &mut Vec<Statement>) {
+        walk_statements_mut(self, statements);
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement);
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+    }
+
+    fn visit_term_mut(&mut self, term: &mut Term) {
+        walk_term_mut(self, term);
+    }
+
+    fn visit_subroutine_call_mut(&mut self, call: &mut SubroutineCall) {
+        walk_subroutine_call_mut(self, call);
+    }
+
+    fn visit_type_mut(&mut self, var_type: &mut Type) {
+        walk_type_mut(self, var_type);
+    }
+
+    fn visit_ident_mut(&mut self, _ident: &mut Ident) {}
+}
+
+pub fn walk_class_mut<V: MutVisitor + ?Sized>(visitor: &mut V, class: &mut Class) {
+    visitor.visit_ident_mut(&mut class.name);
+    for var_dec in &mut class.var_decs {
+        visitor.visit_class_var_dec_mut(var_dec);
+    }
+    for subroutine in &mut class.subroutines {
+        visitor.visit_subroutine_mut(subroutine);
+    }
+}
+
+pub fn walk_class_var_dec_mut<V: MutVisitor + ?Sized>(visitor: &mut V, var_dec: &mut ClassVarDec) {
+    visitor.visit_type_mut(&mut var_dec.var_type);
+    for name in &mut var_dec.names {
+        visitor.visit_ident_mut(name);
+    }
+}
+
+pub fn walk_subroutine_mut<V: MutVisitor + ?Sized>(visitor: &mut V, subroutine: &mut SubroutineDec) {
+    if let Some(ref mut return_type) = subroutine.return_type {
+        visitor.visit_type_mut(return_type);
+    }
+    visitor.visit_ident_mut(&mut subroutine.name);
+    for parameter in &mut subroutine.parameters {
+        visitor.visit_parameter_mut(parameter);
+    }
+    for var_dec in &mut subroutine.locals {
+        visitor.visit_var_dec_mut(var_dec);
+    }
+    visitor.visit_statements_mut(&mut subroutine.body);
+}
+
+pub fn walk_parameter_mut<V: MutVisitor + ?Sized>(visitor: &mut V, parameter: &mut Parameter) {
+    visitor.visit_type_mut(&mut parameter.var_type);
+    visitor.visit_ident_mut(&mut parameter.name);
+}
+
+pub fn walk_var_dec_mut<V: MutVisitor + ?Sized>(visitor: &mut V, var_dec: &mut VarDec) {
+    visitor.visit_type_mut(&mut var_dec.var_type);
+    for name in &mut var_dec.names {
+        visitor.visit_ident_mut(name);
+    }
+}
+
+pub fn walk_statements_mut<V: MutVisitor + ?Sized>(visitor: &mut V, statements: &mut Vec<Statement>) {
+    for statement in statements {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+pub fn walk_statement_mut<V: MutVisitor + ?Sized>(visitor: &mut V, statement: &mut Statement) {
+    match statement.kind {
+        StatementKind::Let { ref mut target, ref mut index, ref mut value } => {
+            visitor.visit_ident_mut(target);
+            if let Some(ref mut index) = *index {
+                visitor.visit_expression_mut(index);
+            }
+            visitor.visit_expression_mut(value);
+        }
+        StatementKind::If { ref mut condition, ref mut then_body, ref mut else_body } => {
+            visitor.visit_expression_mut(condition);
+            visitor.visit_statements_mut(then_body);
+            if let Some(ref mut else_body) = *else_body {
+                visitor.visit_statements_mut(else_body);
+            }
+        }
+        StatementKind::While { ref mut condition, ref mut body } => {
+            visitor.visit_expression_mut(condition);
+            visitor.visit_statements_mut(body);
+        }
+        StatementKind::Do(ref mut call) => visitor.visit_subroutine_call_mut(call),
+        StatementKind::Return(ref mut value) => {
+            if let Some(ref mut value) = *value {
+                visitor.visit_expression_mut(value);
+            }
+        }
+    }
+}
+
+pub fn walk_expression_mut<V: MutVisitor + ?Sized>(visitor: &mut V, expression: &mut Expression) {
+    visitor.visit_term_mut(&mut expression.first);
+    for (_, term) in &mut expression.rest {
+        visitor.visit_term_mut(term);
+    }
+}
+
+pub fn walk_term_mut<V: MutVisitor + ?Sized>(visitor: &mut V, term: &mut Term) {
+    match term.kind {
+        TermKind::IntConst(_) | TermKind::StringConst(_) | TermKind::KeywordConst(_) => (),
+        TermKind::Var(ref mut name) => visitor.visit_ident_mut(name),
+        TermKind::Index(ref mut name, ref mut index) => {
+            visitor.visit_ident_mut(name);
+            visitor.visit_expression_mut(index);
+        }
+        TermKind::Call(ref mut call) => visitor.visit_subroutine_call_mut(call),
+        TermKind::Paren(ref mut expression) => visitor.visit_expression_mut(expression),
+        TermKind::Unary(_, ref mut inner) => visitor.visit_term_mut(inner),
+    }
+}
+
+pub fn walk_subroutine_call_mut<V: MutVisitor + ?Sized>(visitor: &mut V, call: &mut SubroutineCall) {
+    if let Some(ref mut receiver) = call.receiver {
+        visitor.visit_ident_mut(receiver);
+    }
+    visitor.visit_ident_mut(&mut call.name);
+    for arg in &mut call.args {
+        visitor.visit_expression_mut(arg);
+    }
+}
+
+pub fn walk_type_mut<V: MutVisitor + ?Sized>(visitor: &mut V, var_type: &mut Type) {
+    if let Type::Class(ref mut class) = *var_type {
+        visitor.visit_ident_mut(class);
+    }
+}
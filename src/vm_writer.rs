@@ -1,5 +1,9 @@
+use vfs::OutputSink;
+
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
+use std::io::BufWriter;
 use std::path::Path;
 
 #[derive(Clone, Copy)]
@@ -27,8 +31,10 @@ pub enum Command {
     Not,
 }
 
-pub struct VMWriter {
-    outfile: File,
+pub struct VMWriter<W: Write> {
+    outfile: W,
+    // The first error that happened while writing, reported by finish
+    error: Option<io::Error>,
 }
 
 fn segment_string(seg: Segment) -> &'static str {
@@ -58,15 +64,46 @@ fn command_string(com: Command) -> &'static str {
     }
 }
 
-impl VMWriter {
-    pub fn new(path: &Path) -> VMWriter {
+impl VMWriter<BufWriter<File>> {
+    pub fn new(path: &Path) -> io::Result<VMWriter<BufWriter<File>>> {
+        Ok(VMWriter::from_writer(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl VMWriter<Box<dyn Write>> {
+    /// Create a file in an output sink and write to it
+    pub fn create(sink: &mut dyn OutputSink, path: &Path) -> io::Result<VMWriter<Box<dyn Write>>> {
+        Ok(VMWriter::from_writer(sink.create(path)?))
+    }
+}
+
+impl<W: Write> VMWriter<W> {
+    pub fn from_writer(outfile: W) -> VMWriter<W> {
         VMWriter {
-            outfile: File::create(path).unwrap(),
+            outfile,
+            error: None,
         }
     }
-    
+
+    /// Get back the writer, e.g. to read the code written to a Vec<u8>
+    pub fn into_inner(self) -> W {
+        self.outfile
+    }
+
     fn write_string(&mut self, data: String) {
-        self.outfile.write_all(data.as_bytes()).unwrap();
+        if self.error.is_none() {
+            if let Err(why) = self.outfile.write_all(data.as_bytes()) {
+                self.error = Some(why);
+            }
+        }
+    }
+
+    /// Flush the output and report the first error that happened while writing
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(why) => Err(why),
+            None => self.outfile.flush(),
+        }
     }
 
     pub fn write_push(&mut self, seg: Segment, index: i32) {
@@ -1,9 +1,15 @@
 #![allow(dead_code)]
+use ast::*;
+use error::*;
 use jack_analyzer::*;
 use symbol_table::*;
+use vfs::OutputSink;
 
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
 
 pub fn tag_string(tag_name: &str, content: &str) -> String {
     format!("<{0}> {1} </{0}>\n", tag_name, content)
@@ -55,34 +61,452 @@ pub fn kind_string(kind: Kind) -> &'static str {
     }
 }
 
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '&' => escaped += "&amp;",
+            '"' => escaped += "&quot;",
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+/// Escape the characters of a string constant, writing newlines and tabs
+/// from escapes as the escapes so the constant stays on one line
+pub fn escape_string(text: &str) -> String {
+    escape(text).replace('\n', "\\n").replace('\t', "\\t")
+}
+
 pub fn get_tag_data(analyzer: &JackAnalyzer, token_type: &TokenType) -> String {
     match *token_type {
         TokenType::Keyword => keyword_to_str(&analyzer.key_word().unwrap()).to_string(),
-        TokenType::Symbol => match analyzer.symbol() {
-            '<' => "&lt;".to_string(),
-            '>' => "&gt;".to_string(),
-            '&' => "&amp;".to_string(),
-            other => other.to_string(),
-        },
+        TokenType::Symbol => escape(&analyzer.symbol().to_string()),
         TokenType::Identifier => analyzer.identifier(),
         TokenType::IntConst => analyzer.int_val().to_string(),
-        TokenType::StringConst => analyzer.string_val(),
+        TokenType::StringConst => escape_string(&analyzer.string_val()),
     }
 }
 
 pub fn make_tag_string(analyzer: &JackAnalyzer) -> String {
     let token_type = analyzer.token_type();
-    let tag_data = get_tag_data(&analyzer, &token_type);
+    let tag_data = get_tag_data(analyzer, &token_type);
     tag_string(get_tag_name(&token_type), &tag_data)
 }
 
-pub fn write_tag_string(analyzer: &JackAnalyzer, outfile: &mut File) {
-    outfile.write_all(make_tag_string(&analyzer).as_bytes()).unwrap();
+pub fn write_tag_string<W: Write>(analyzer: &JackAnalyzer, outfile: &mut W) -> io::Result<()> {
+    outfile.write_all(make_tag_string(analyzer).as_bytes())
+}
+
+/// Write every token in the input file in the format of the nand2tetris
+/// project 10 XxxT.xml files
+pub fn write_tokens(mut analyzer: JackAnalyzer, sink: &mut dyn OutputSink, outfile: &Path)
+                    -> Result<(), Vec<CompileError>> {
+    let outfile_name = outfile.display().to_string();
+    let io_error = |why: io::Error| vec![CompileError::io(&outfile_name, &why)];
+    let mut out = sink.create(outfile).map_err(&io_error)?;
+
+    let mut errors = Vec::new();
+    out.write_all(b"<tokens>\n").map_err(&io_error)?;
+    loop {
+        match analyzer.advance() {
+            Ok(()) => write_tag_string(&analyzer, &mut out).map_err(&io_error)?,
+            Err(error) if analyzer.at_eof() => {
+                // An unterminated comment is reported at the end of the file
+                if error.code != E_UNEXPECTED_EOF {
+                    errors.push(error);
+                }
+                break;
+            }
+            Err(error) => errors.push(error),
+        }
+    }
+    out.write_all(b"</tokens>\n").map_err(&io_error)?;
+    out.flush().map_err(&io_error)?;
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// What an identifier refers to, for the symbol table test stage of project 11
+pub struct Annotation {
+    // One of class, subroutine, static, field, arg or var
+    pub category: &'static str,
+    // Index in the symbol table, only for variables
+    pub index: Option<i32>,
+    // Whether the identifier is being defined or used
+    pub defined: bool,
+}
+
+pub fn id_string(name: &str, annotation: &Annotation) -> String {
+    let index = match annotation.index {
+        Some(index) => format!(" index=\"{}\"", index),
+        None => String::new(),
+    };
+    let usage = if annotation.defined { "defined" } else { "used" };
+    format!("<identifier category=\"{}\"{} usage=\"{}\"> {} </identifier>\n",
+            annotation.category, index, usage, name)
 }
 
-pub fn write_id_string(analyzer: &JackAnalyzer, outfile: &mut File, symbol_table: &SymbolTable) {
-    let name = analyzer.identifier();
-    outfile.write_all(format!("<{0}> {1} {2} {3} </{0}>\n", "identifier", name,
-                              symbol_table.type_of(&name),
-                              kind_string(symbol_table.kind_of(&name))).as_bytes()).unwrap();
+/// Writes the nested parse tree XML of project 10, indented by two spaces
+/// for every level
+pub struct XmlWriter {
+    out: Box<dyn Write>,
+    indent: usize,
+    // The first error that happened while writing, reported by finish
+    error: Option<io::Error>,
+}
+
+impl XmlWriter {
+    pub fn new(path: &Path) -> io::Result<XmlWriter> {
+        Ok(XmlWriter::from_writer(Box::new(BufWriter::new(File::create(path)?))))
+    }
+
+    pub fn from_writer(out: Box<dyn Write>) -> XmlWriter {
+        XmlWriter {
+            out,
+            indent: 0,
+            error: None,
+        }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.error.is_none() {
+            let indented = format!("{:1$}{2}", "", self.indent, line);
+            if let Err(why) = self.out.write_all(indented.as_bytes()) {
+                self.error = Some(why);
+            }
+        }
+    }
+
+    pub fn open(&mut self, tag_name: &str) {
+        self.write_line(&format!("<{}>\n", tag_name));
+        self.indent += 2;
+    }
+
+    pub fn close(&mut self, tag_name: &str) {
+        self.indent -= 2;
+        self.write_line(&format!("</{}>\n", tag_name));
+    }
+
+    pub fn keyword(&mut self, keyword: &str) {
+        self.write_line(&tag_string("keyword", keyword));
+    }
+
+    pub fn symbol(&mut self, sym: char) {
+        self.write_line(&tag_string("symbol", &escape(&sym.to_string())));
+    }
+
+    /// Write an identifier, with what it refers to if it is annotated
+    pub fn identifier(&mut self, name: &str, annotation: Option<&Annotation>) {
+        match annotation {
+            Some(annotation) => self.write_line(&id_string(name, annotation)),
+            None => self.write_line(&tag_string("identifier", name)),
+        }
+    }
+
+    pub fn int_const(&mut self, val: i32) {
+        self.write_line(&tag_string("integerConstant", &val.to_string()));
+    }
+
+    pub fn string_const(&mut self, string: &str) {
+        self.write_line(&tag_string("stringConstant", &escape_string(string)));
+    }
+
+    /// Flush the output and report the first error that happened while writing
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(why) => Err(why),
+            None => self.out.flush(),
+        }
+    }
+}
+
+/// Write the parse tree of a class in the format of the nand2tetris project
+/// 10 Xxx.xml files. If `annotated` is set identifiers also get their
+/// category, symbol table index and whether they are being defined or used.
+pub fn write_parse_tree(class: &Class, sink: &mut dyn OutputSink, outfile: &Path, annotated: bool)
+                        -> Result<(), Vec<CompileError>> {
+    let outfile_name = outfile.display().to_string();
+    let out = sink.create(outfile).map_err(|why| vec![CompileError::io(&outfile_name, &why)])?;
+    let xml = XmlWriter::from_writer(out);
+    let mut writer = TreeWriter {
+        xml,
+        symbol_table: if annotated { Some(SymbolTable::new()) } else { None },
+    };
+    writer.class(class);
+    writer.xml.finish().map_err(|why| vec![CompileError::io(&outfile_name, &why)])
+}
+
+// Walks the syntax tree and writes every node, putting back the keywords and
+// symbols that the tree leaves out
+struct TreeWriter {
+    xml: XmlWriter,
+    // Only kept when identifiers are annotated
+    symbol_table: Option<SymbolTable>,
+}
+
+impl TreeWriter {
+    // Write the name of a class or subroutine
+    fn name(&mut self, name: &Ident, category: &'static str, defined: bool) {
+        let annotation = self.symbol_table.as_ref().map(|_| Annotation { category, index: None, defined });
+        self.xml.identifier(&name.name, annotation.as_ref());
+    }
+
+    // Write the name of a variable being declared and add it to the symbol table
+    fn define(&mut self, name: &Ident, var_type: &Type, kind: Kind) {
+        let annotation = self.symbol_table.as_mut().map(|table| {
+            // Names defined twice are left without an index
+            let index = table.define(&name.name, var_type.name(), kind, None).ok();
+            Annotation { category: kind_string(kind), index, defined: true }
+        });
+        self.xml.identifier(&name.name, annotation.as_ref());
+    }
+
+    // Write a use of a variable. Undefined variables are left without annotation.
+    fn variable(&mut self, name: &Ident) {
+        let annotation = self.symbol_table.as_ref().and_then(|table| {
+            match table.kind_of(&name.name) {
+                Kind::None => None,
+                kind => Some(Annotation { category: kind_string(kind), index: table.index_of(&name.name), defined: false }),
+            }
+        });
+        self.xml.identifier(&name.name, annotation.as_ref());
+    }
+
+    fn is_variable(&self, name: &Ident) -> bool {
+        self.symbol_table.as_ref().is_some_and(|table| table.kind_of(&name.name) != Kind::None)
+    }
+
+    fn var_type(&mut self, var_type: &Type) {
+        match *var_type {
+            Type::Class(ref class) => self.name(class, "class", false),
+            ref other => self.xml.keyword(other.name()),
+        }
+    }
+
+    // Write the names of a declaration separated by commas
+    fn var_names(&mut self, names: &[Ident], var_type: &Type, kind: Kind) {
+        for (i, name) in names.iter().enumerate() {
+            if i > 0 {
+                self.xml.symbol(',');
+            }
+            self.define(name, var_type, kind);
+        }
+    }
+
+    fn class(&mut self, class: &Class) {
+        self.xml.open("class");
+        self.xml.keyword("class");
+        self.name(&class.name, "class", true);
+        self.xml.symbol('{');
+        for var_dec in &class.var_decs {
+            self.class_var_dec(var_dec);
+        }
+        for subroutine in &class.subroutines {
+            self.subroutine(subroutine);
+        }
+        self.xml.symbol('}');
+        self.xml.close("class");
+    }
+
+    fn class_var_dec(&mut self, var_dec: &ClassVarDec) {
+        self.xml.open("classVarDec");
+        self.xml.keyword(match var_dec.kind {
+            ClassVarKind::Static => "static",
+            ClassVarKind::Field => "field",
+        });
+        self.var_type(&var_dec.var_type);
+        self.var_names(&var_dec.names, &var_dec.var_type, class_var_kind(var_dec.kind));
+        self.xml.symbol(';');
+        self.xml.close("classVarDec");
+    }
+
+    fn subroutine(&mut self, subroutine: &SubroutineDec) {
+        if let Some(ref mut table) = self.symbol_table {
+            table.start_subroutine();
+            if subroutine.kind == SubroutineKind::Method {
+                // Make room for this pointer in parameters
+                let _ = table.define("this", "", Kind::Arg, None);
+            }
+        }
+
+        self.xml.open("subroutineDec");
+        self.xml.keyword(subroutine.kind.keyword());
+        match subroutine.return_type {
+            Some(ref return_type) => self.var_type(return_type),
+            None => self.xml.keyword("void"),
+        }
+        self.name(&subroutine.name, "subroutine", true);
+
+        self.xml.symbol('(');
+        self.xml.open("parameterList");
+        for (i, parameter) in subroutine.parameters.iter().enumerate() {
+            if i > 0 {
+                self.xml.symbol(',');
+            }
+            self.var_type(&parameter.var_type);
+            self.define(&parameter.name, &parameter.var_type, Kind::Arg);
+        }
+        self.xml.close("parameterList");
+        self.xml.symbol(')');
+
+        self.xml.open("subroutineBody");
+        self.xml.symbol('{');
+        for var_dec in &subroutine.locals {
+            self.xml.open("varDec");
+            self.xml.keyword("var");
+            self.var_type(&var_dec.var_type);
+            self.var_names(&var_dec.names, &var_dec.var_type, Kind::Var);
+            self.xml.symbol(';');
+            self.xml.close("varDec");
+        }
+        self.statements(&subroutine.body);
+        self.xml.symbol('}');
+        self.xml.close("subroutineBody");
+        self.xml.close("subroutineDec");
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        self.xml.open("statements");
+        for statement in statements {
+            self.statement(statement);
+        }
+        self.xml.close("statements");
+    }
+
+    // Write a block of statements in braces
+    fn block(&mut self, statements: &[Statement]) {
+        self.xml.symbol('{');
+        self.statements(statements);
+        self.xml.symbol('}');
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement.kind {
+            StatementKind::Let { ref target, ref index, ref value } => {
+                self.xml.open("letStatement");
+                self.xml.keyword("let");
+                self.variable(target);
+                if let Some(ref index) = *index {
+                    self.xml.symbol('[');
+                    self.expression(index);
+                    self.xml.symbol(']');
+                }
+                self.xml.symbol('=');
+                self.expression(value);
+                self.xml.symbol(';');
+                self.xml.close("letStatement");
+            }
+            StatementKind::If { ref condition, ref then_body, ref else_body } => {
+                self.xml.open("ifStatement");
+                self.xml.keyword("if");
+                self.xml.symbol('(');
+                self.expression(condition);
+                self.xml.symbol(')');
+                self.block(then_body);
+                if let Some(ref else_body) = *else_body {
+                    self.xml.keyword("else");
+                    self.block(else_body);
+                }
+                self.xml.close("ifStatement");
+            }
+            StatementKind::While { ref condition, ref body } => {
+                self.xml.open("whileStatement");
+                self.xml.keyword("while");
+                self.xml.symbol('(');
+                self.expression(condition);
+                self.xml.symbol(')');
+                self.block(body);
+                self.xml.close("whileStatement");
+            }
+            StatementKind::Do(ref call) => {
+                self.xml.open("doStatement");
+                self.xml.keyword("do");
+                self.subroutine_call(call);
+                self.xml.symbol(';');
+                self.xml.close("doStatement");
+            }
+            StatementKind::Return(ref value) => {
+                self.xml.open("returnStatement");
+                self.xml.keyword("return");
+                if let Some(ref value) = *value {
+                    self.expression(value);
+                }
+                self.xml.symbol(';');
+                self.xml.close("returnStatement");
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        self.xml.open("expression");
+        self.term(&expression.first);
+        for &(op, ref term) in &expression.rest {
+            self.xml.symbol(op.symbol());
+            self.term(term);
+        }
+        self.xml.close("expression");
+    }
+
+    fn term(&mut self, term: &Term) {
+        self.xml.open("term");
+        match term.kind {
+            TermKind::IntConst(val) => self.xml.int_const(val),
+            TermKind::StringConst(ref string) => self.xml.string_const(string),
+            TermKind::KeywordConst(keyword) => self.xml.keyword(match keyword {
+                KeywordConst::True => "true",
+                KeywordConst::False => "false",
+                KeywordConst::Null => "null",
+                KeywordConst::This => "this",
+            }),
+            TermKind::Var(ref name) => self.variable(name),
+            TermKind::Index(ref name, ref index) => {
+                self.variable(name);
+                self.xml.symbol('[');
+                self.expression(index);
+                self.xml.symbol(']');
+            }
+            TermKind::Call(ref call) => self.subroutine_call(call),
+            TermKind::Paren(ref expression) => {
+                self.xml.symbol('(');
+                self.expression(expression);
+                self.xml.symbol(')');
+            }
+            TermKind::Unary(op, ref inner) => {
+                self.xml.symbol(op.symbol());
+                self.term(inner);
+            }
+        }
+        self.xml.close("term");
+    }
+
+    fn subroutine_call(&mut self, call: &SubroutineCall) {
+        if let Some(ref receiver) = call.receiver {
+            if self.is_variable(receiver) {
+                self.variable(receiver);
+            } else {
+                self.name(receiver, "class", false);
+            }
+            self.xml.symbol('.');
+        }
+        self.name(&call.name, "subroutine", false);
+
+        self.xml.symbol('(');
+        self.xml.open("expressionList");
+        for (i, arg) in call.args.iter().enumerate() {
+            if i > 0 {
+                self.xml.symbol(',');
+            }
+            self.expression(arg);
+        }
+        self.xml.close("expressionList");
+        self.xml.symbol(')');
+    }
 }
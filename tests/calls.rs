@@ -0,0 +1,172 @@
+// Tests for how calls are resolved to functions, methods and constructors
+
+extern crate jackcompiler;
+
+mod common;
+
+use jackcompiler::compilation_engine::CompilationEngine;
+use common::compile;
+use jackcompiler::error::*;
+use jackcompiler::jack_analyzer::JackAnalyzer;
+use jackcompiler::parser::Parser;
+
+const COUNTER: &str = "
+    class Main {
+        field int count;
+
+        constructor Main new() {
+            do reset();
+            return this;
+        }
+
+        method void reset() {
+            let count = 0;
+            return;
+        }
+
+        method void add(int n) {
+            do reset();
+            let count = Main.sum(count, n);
+            return;
+        }
+
+        function int sum(int a, int b) {
+            return a + b;
+        }
+
+        function int twice(int a) {
+            return sum(a, a);
+        }
+    }";
+
+#[test]
+fn unqualified_calls() {
+    let code = compile(COUNTER).unwrap();
+    // Methods are called on this, from constructors too
+    assert!(code.contains("function Main.new 0\npush constant 1\ncall Memory.alloc 1\npop pointer 0\n\
+                           push pointer 0\ncall Main.reset 1\n"));
+    assert!(code.contains("function Main.add 0\npush argument 0\npop pointer 0\npush pointer 0\ncall Main.reset 1\n"));
+    // Functions are not, and there is no this in a function anyway
+    assert!(code.contains("function Main.twice 0\npush argument 0\npush argument 0\ncall Main.sum 2\n"));
+}
+
+#[test]
+fn methods_from_functions() {
+    let source = "
+        class Main {
+            method void run() { return; }
+            function void main() {
+                do run();
+                return;
+            }
+        }";
+    let diagnostics = jackcompiler::compile_source("Main.jack", source).unwrap_err();
+    let error = &diagnostics.errors[0];
+    assert_eq!(error.code, E_INVALID_CALL);
+    assert_eq!(error.message, "Cannot call method run from function Main.main");
+    assert_eq!(error.notes[0].span.as_ref().unwrap().start.line, 3);
+}
+
+#[test]
+fn methods_through_the_class() {
+    let source = "
+        class Main {
+            method void run() { return; }
+            function void main() {
+                var String s;
+                do Main.run();
+                do String.length();
+                return;
+            }
+        }";
+    assert_eq!(compile(source), Err(vec![E_INVALID_CALL, E_INVALID_CALL]));
+}
+
+#[test]
+fn functions_through_an_object() {
+    let source = "
+        class Main {
+            function void main() {
+                var Main m;
+                var String s;
+                do m.main();
+                let s = s.new(3);
+                do s.backSpace();
+                return;
+            }
+        }";
+    let diagnostics = jackcompiler::compile_source("Main.jack", source).unwrap_err();
+    let messages: Vec<&str> = diagnostics.errors.iter().map(|error| &*error.message).collect();
+    assert_eq!(messages, vec!["Cannot call function Main.main on the object m",
+                              "Cannot call constructor String.new on the object s",
+                              "Cannot call function String.backSpace on the object s"]);
+    assert_eq!(diagnostics.errors[1].help, vec!["constructors are called on their class, like String.new()"]);
+}
+
+#[test]
+fn calls_to_unknown_classes() {
+    let source = "
+        class Main {
+            function void main() {
+                var Game game;
+                do Game.start();
+                do game.run(1);
+                return;
+            }
+        }";
+    let code = compile(source).unwrap();
+    assert!(code.contains("call Game.start 0\n"));
+    assert!(code.contains("push local 0\npush constant 1\ncall Game.run 2\n"));
+}
+
+#[test]
+fn calls_without_a_class_index() {
+    // Without an index the kind of the subroutine in the class is guessed
+    // from the caller
+    let class = Parser::new(JackAnalyzer::from_source("Main.jack", COUNTER)).parse_class().unwrap();
+    let mut compiler = CompilationEngine::from_writer("Main.vm", Vec::new());
+    compiler.compile_class(&class).unwrap();
+    let code = String::from_utf8(compiler.into_inner()).unwrap();
+    assert!(code.contains("push pointer 0\ncall Main.reset 1\n"));
+    assert!(code.contains("push argument 0\npush argument 0\ncall Main.sum 2\n"));
+}
+
+#[test]
+fn argument_counts() {
+    let source = "
+        class Main {
+            function void main() {
+                var Main m;
+                do Output.printInt(1, 2);
+                do Math.max(3);
+                do Main.sum(1, 2);
+                do m.add();
+                do Screen.clearScreen();
+                return;
+            }
+            function int sum(int a, int b) { return a + b; }
+            method void add(int n) { return; }
+        }";
+    let diagnostics = jackcompiler::compile_source("Main.jack", source).unwrap_err();
+    let messages: Vec<&str> = diagnostics.errors.iter().map(|error| &*error.message).collect();
+    assert_eq!(messages, vec!["Output.printInt takes 1 argument but 2 were given",
+                              "Math.max takes 2 arguments but 1 was given",
+                              "Main.add takes 1 argument but 0 were given"]);
+    assert!(diagnostics.errors.iter().all(|error| error.code == E_ARGUMENT_COUNT));
+}
+
+#[test]
+fn argument_counts_show_the_signature() {
+    let source = "class Main {\n    function void main() {\n        do Memory.poke(0);\n        do f();\n        return;\n    }\n    function void f(int a, Array b) { return; }\n}";
+    let diagnostics = jackcompiler::compile_source("Main.jack", source).unwrap_err();
+
+    // The OS is not in any file, so the note has no span
+    let os_note = &diagnostics.errors[0].notes[0];
+    assert_eq!(os_note.message, "Memory.poke is declared as function void poke(int address, int value)");
+    assert!(os_note.span.is_none());
+
+    let note = &diagnostics.errors[1].notes[0];
+    assert_eq!(note.message, "Main.f is declared as function void f(int a, Array b)");
+    assert_eq!(note.span.as_ref().unwrap().start.line, 7);
+    assert!(diagnostics.render(false).contains("= note: Memory.poke is declared as function void poke("));
+}
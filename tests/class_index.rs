@@ -0,0 +1,189 @@
+// Tests for the index of the classes in a project and the checks on calls
+// between them
+
+extern crate jackcompiler;
+
+mod common;
+
+use common::{compile_project, compile_project_with};
+use jackcompiler::ast::SubroutineKind;
+use jackcompiler::class_index::{ClassIndex, VariableInfo};
+use jackcompiler::error::*;
+use jackcompiler::jack_analyzer::JackAnalyzer;
+use jackcompiler::parser::Parser;
+use jackcompiler::CompileOptions;
+
+const POINT: &str = "
+    class Point {
+        static int count;
+        field int x, y;
+
+        constructor Point new(int ax, int ay) {
+            let x = ax;
+            let y = ay;
+            let count = count + 1;
+            return this;
+        }
+
+        method int getX() { return x; }
+
+        method Point plus(Point other) {
+            return Point.new(x + other.getX(), y);
+        }
+
+        function void reset() {
+            let count = 0;
+            return;
+        }
+    }";
+
+fn index(sources: &[&str]) -> (ClassIndex, Vec<&'static str>) {
+    let mut index = ClassIndex::new();
+    let mut errors = Vec::new();
+    for source in sources {
+        let class = Parser::new(JackAnalyzer::from_source("Test.jack", source)).parse_class().unwrap();
+        if let Err(index_errors) = index.add_class(&class) {
+            errors.extend(index_errors.iter().map(|error| error.code));
+        }
+    }
+    (index, errors)
+}
+
+fn names(variables: &[VariableInfo]) -> Vec<&str> {
+    variables.iter().map(|variable| &*variable.name).collect()
+}
+
+#[test]
+fn classes_are_indexed() {
+    let (index, errors) = index(&[POINT]);
+    assert!(errors.is_empty());
+    assert!(index.contains("Point"));
+    assert!(!index.contains("Main"));
+
+    let point = index.get("Point").unwrap();
+    assert_eq!(names(&point.statics), vec!["count"]);
+    assert_eq!(names(&point.fields), vec!["x", "y"]);
+    assert_eq!(point.subroutines.len(), 4);
+}
+
+#[test]
+fn signatures() {
+    let (index, _) = index(&[POINT]);
+
+    let new = index.subroutine("Point", "new").unwrap();
+    assert_eq!(new.kind, SubroutineKind::Constructor);
+    assert_eq!(new.return_type, Some("Point".to_string()));
+    let parameters: Vec<(&str, &str)> = new.parameters.iter()
+        .map(|parameter| (&*parameter.type_name, &*parameter.name))
+        .collect();
+    assert_eq!(parameters, vec![("int", "ax"), ("int", "ay")]);
+
+    let reset = index.subroutine("Point", "reset").unwrap();
+    assert_eq!(reset.kind, SubroutineKind::Function);
+    assert_eq!(reset.return_type, None);
+    assert!(reset.parameters.is_empty());
+
+    assert_eq!(index.subroutine("Point", "plus").unwrap().kind, SubroutineKind::Method);
+    assert!(index.subroutine("Point", "minus").is_none());
+    assert!(index.subroutine("Line", "new").is_none());
+}
+
+#[test]
+fn duplicate_classes() {
+    let (index, errors) = index(&[POINT, "class Point { field boolean b; }"]);
+    assert_eq!(errors, vec![E_DUPLICATE_DEFINITION]);
+    // The first definition is kept
+    assert_eq!(index.get("Point").unwrap().fields.len(), 2);
+}
+
+#[test]
+fn duplicate_subroutines() {
+    let source = "
+        class Main {
+            function void f() { return; }
+            method int f() { return 1; }
+        }";
+    let (index, errors) = index(&[source]);
+    assert_eq!(errors, vec![E_DUPLICATE_DEFINITION]);
+    assert_eq!(index.subroutine("Main", "f").unwrap().kind, SubroutineKind::Function);
+}
+
+#[test]
+fn calls_between_classes() {
+    let main = "
+        class Main {
+            function void main() {
+                var Point p;
+                let p = Point.new(1, 2);
+                let p = p.plus(p);
+                do Point.reset();
+                do Output.printInt(p.getX());
+                return;
+            }
+        }";
+    let code = compile_project(&[("Main.jack", main), ("Point.jack", POINT)]).unwrap();
+    assert_eq!(code.len(), 2);
+    assert!(code[0].contains("call Point.plus 2\n"));
+}
+
+#[test]
+fn calls_to_missing_subroutines() {
+    let main = "
+        class Main {
+            function void main() {
+                var Point p;
+                let p = Point.create(1, 2);
+                do p.getY();
+                do Main.run();
+                do run();
+                return;
+            }
+        }";
+    assert_eq!(compile_project(&[("Main.jack", main), ("Point.jack", POINT)]),
+               Err(vec![E_UNDEFINED_SUBROUTINE; 4]));
+}
+
+#[test]
+fn calls_to_classes_outside_the_project_are_not_checked() {
+    let main = "
+        class Main {
+            function void main() {
+                var Point p;
+                do Point.create(1, 2);
+                do p.getY();
+                return;
+            }
+        }";
+    assert!(compile_project(&[("Main.jack", main)]).is_ok());
+}
+
+#[test]
+fn classes_outside_a_whole_program_are_undefined() {
+    let main = "
+        class Main {
+            function void main() {
+                var Point p;
+                do Point.new(1, 2);
+                do p.getX();
+                do Output.println();
+                return;
+            }
+        }";
+    let options = CompileOptions { whole_program: true, ..CompileOptions::default() };
+    // The call on p is not reported again after its declaration
+    assert_eq!(compile_project_with(&[("Main.jack", main)], &options), Err(vec![E_UNDEFINED_CLASS, E_UNDEFINED_CLASS]));
+    assert!(compile_project_with(&[("Main.jack", main), ("Point.jack", POINT)], &options).is_ok());
+
+    // A class that could not be parsed is not reported as undefined too
+    let point = "class Point { constructor Point new(int x, int y) { return this; } method int getX( }";
+    assert_eq!(compile_project_with(&[("Main.jack", main), ("Point.jack", point)], &options), Err(vec![E_SYNTAX]));
+}
+
+#[test]
+fn errors_from_every_file_are_reported() {
+    let main = "class Main { function void main() { do Point.create(); return; } }";
+    let broken = "class Broken { function void f() { let = 1; return; } }";
+    // The errors are in the order of the files
+    assert_eq!(compile_project(&[("Main.jack", main), ("Point.jack", POINT), ("Broken.jack", broken)]),
+               Err(vec![E_UNDEFINED_SUBROUTINE, E_SYNTAX]));
+}
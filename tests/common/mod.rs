@@ -0,0 +1,25 @@
+// Helpers shared by the tests that compile sources and check the error
+// codes. Not every test uses every helper.
+#![allow(dead_code)]
+
+use jackcompiler;
+use jackcompiler::CompileOptions;
+
+// The code for a single class in Main.jack, or the codes of its errors
+pub fn compile(source: &str) -> Result<String, Vec<&'static str>> {
+    jackcompiler::compile_source("Main.jack", source)
+        .map(|output| output.code)
+        .map_err(|diagnostics| diagnostics.errors.iter().map(|error| error.code).collect())
+}
+
+// The code for every class of a project, or the codes of its errors
+pub fn compile_project(sources: &[(&str, &str)]) -> Result<Vec<String>, Vec<&'static str>> {
+    compile_project_with(sources, &CompileOptions::default())
+}
+
+pub fn compile_project_with(sources: &[(&str, &str)], options: &CompileOptions)
+                            -> Result<Vec<String>, Vec<&'static str>> {
+    jackcompiler::compile_project(sources, options)
+        .map(|outputs| outputs.into_iter().map(|output| output.code).collect())
+        .map_err(|diagnostics| diagnostics.errors.iter().map(|error| error.code).collect())
+}
@@ -0,0 +1,352 @@
+// Conformance tests for the lexical grammar of the Jack language
+// specification
+
+extern crate jackcompiler;
+
+use jackcompiler::error::*;
+use jackcompiler::jack_analyzer::{JackAnalyzer, LexOptions, TokenType};
+
+use TokenType::*;
+
+// Every token of a source with its type and text, or the code of the error
+// reported for it
+fn lex(source: &str) -> Vec<Result<(TokenType, String), &'static str>> {
+    let mut analyzer = JackAnalyzer::from_source("Test.jack", source);
+    let mut tokens = Vec::new();
+    loop {
+        match analyzer.advance() {
+            Ok(()) => {
+                let token = analyzer.token();
+                tokens.push(Ok((token.kind, token.text.clone())));
+            }
+            Err(error) if analyzer.at_eof() => {
+                if error.code != E_UNEXPECTED_EOF {
+                    tokens.push(Err(error.code));
+                }
+                return tokens;
+            }
+            Err(error) => tokens.push(Err(error.code)),
+        }
+    }
+}
+
+// Like lex, with escapes in string constants allowed
+fn lex_escapes(source: &str) -> Vec<Result<(TokenType, String), &'static str>> {
+    let options = LexOptions { string_escapes: true };
+    let mut analyzer = JackAnalyzer::from_source_with("Test.jack", source, options);
+    let mut tokens = Vec::new();
+    while !analyzer.at_eof() {
+        match analyzer.advance() {
+            Ok(()) => tokens.push(Ok((analyzer.token_type(), analyzer.string_val()))),
+            Err(error) if analyzer.at_eof() => assert_eq!(error.code, E_UNEXPECTED_EOF),
+            Err(error) => tokens.push(Err(error.code)),
+        }
+    }
+    tokens
+}
+
+fn ok(kind: TokenType, text: &str) -> Result<(TokenType, String), &'static str> {
+    Ok((kind, text.to_string()))
+}
+
+// The types of every token, failing on any error
+fn kinds(source: &str) -> Vec<TokenType> {
+    lex(source).into_iter().map(|token| token.unwrap().0).collect()
+}
+
+#[test]
+fn keywords() {
+    let source = "class constructor function method field static var int char boolean void
+                  true false null this let do if else while return";
+    assert_eq!(kinds(source), vec![Keyword; 21]);
+}
+
+#[test]
+fn keywords_are_case_sensitive_and_whole_words() {
+    assert_eq!(kinds("Class classy _class class_ class1 IF"), vec![Identifier; 6]);
+}
+
+#[test]
+fn symbols() {
+    let symbols = "{}()[].,;+-*/&|<>=~";
+    let tokens = lex(symbols);
+    assert_eq!(tokens.len(), symbols.len());
+    for (token, symbol) in tokens.into_iter().zip(symbols.chars()) {
+        assert_eq!(token, ok(Symbol, &symbol.to_string()));
+    }
+}
+
+#[test]
+fn identifiers() {
+    assert_eq!(lex("x _ _x x_ a1_b2 Foo_Bar __init__"), vec![
+        ok(Identifier, "x"),
+        ok(Identifier, "_"),
+        ok(Identifier, "_x"),
+        ok(Identifier, "x_"),
+        ok(Identifier, "a1_b2"),
+        ok(Identifier, "Foo_Bar"),
+        ok(Identifier, "__init__"),
+    ]);
+}
+
+#[test]
+fn identifiers_are_ascii() {
+    assert_eq!(lex("let größe = 1;"), vec![
+        ok(Keyword, "let"),
+        Err(E_UNEXPECTED_CHAR),
+        ok(Symbol, "="),
+        ok(IntConst, "1"),
+        ok(Symbol, ";"),
+    ]);
+    assert_eq!(lex("Ünit"), vec![Err(E_UNEXPECTED_CHAR)]);
+}
+
+#[test]
+fn identifiers_cannot_start_with_a_digit() {
+    assert_eq!(lex("1abc 2_ 3x4"), vec![Err(E_INVALID_NUMBER); 3]);
+}
+
+#[test]
+fn integer_constants() {
+    assert_eq!(lex("0 7 007 32767"), vec![
+        ok(IntConst, "0"),
+        ok(IntConst, "7"),
+        ok(IntConst, "007"),
+        ok(IntConst, "32767"),
+    ]);
+
+    let mut analyzer = JackAnalyzer::from_source("Test.jack", "32767 007");
+    analyzer.advance().unwrap();
+    assert_eq!(analyzer.int_val(), 32767);
+    analyzer.advance().unwrap();
+    assert_eq!(analyzer.int_val(), 7);
+}
+
+#[test]
+fn integer_constants_out_of_range() {
+    assert_eq!(lex("32768 65536 99999999999999999999999"), vec![Err(E_INT_RANGE); 3]);
+}
+
+#[test]
+fn negative_numbers_are_two_tokens() {
+    assert_eq!(lex("-1"), vec![ok(Symbol, "-"), ok(IntConst, "1")]);
+}
+
+#[test]
+fn string_constants() {
+    assert_eq!(lex(r#""" "hello world" "// not a comment" "/* nor this */" "x = 'y';""#), vec![
+        ok(StringConst, r#""""#),
+        ok(StringConst, r#""hello world""#),
+        ok(StringConst, r#""// not a comment""#),
+        ok(StringConst, r#""/* nor this */""#),
+        ok(StringConst, r#""x = 'y';""#),
+    ]);
+
+    let mut analyzer = JackAnalyzer::from_source("Test.jack", r#""a  b""#);
+    analyzer.advance().unwrap();
+    assert_eq!(analyzer.string_val(), "a  b");
+}
+
+#[test]
+fn unterminated_strings_end_at_the_end_of_the_line() {
+    assert_eq!(lex("\"abc\nx"), vec![Err(E_UNTERMINATED_STRING), ok(Identifier, "x")]);
+    assert_eq!(lex("\"abc\r\nx"), vec![Err(E_UNTERMINATED_STRING), ok(Identifier, "x")]);
+    assert_eq!(lex("\"abc"), vec![Err(E_UNTERMINATED_STRING)]);
+}
+
+#[test]
+fn string_constants_use_the_hack_character_set() {
+    assert_eq!(kinds(r#"" !~09azAZ`{|}\""#), vec![StringConst]);
+    assert_eq!(lex("\"é\" \"tab\there\" \"\u{7f}\" \"ok\""), vec![
+        Err(E_UNSUPPORTED_CHAR),
+        Err(E_UNSUPPORTED_CHAR),
+        Err(E_UNSUPPORTED_CHAR),
+        ok(StringConst, "\"ok\""),
+    ]);
+
+    let mut analyzer = JackAnalyzer::from_source("Test.jack", "  \"caf€ ü\"");
+    let error = analyzer.advance().unwrap_err();
+    // The error points at the first character that is not allowed
+    let span = error.span.unwrap();
+    assert_eq!((span.start.column, span.end.column), (7, 8));
+}
+
+#[test]
+fn backslashes_are_characters_without_escapes() {
+    let mut analyzer = JackAnalyzer::from_source("Test.jack", r#""a\nb\""#);
+    analyzer.advance().unwrap();
+    assert_eq!(analyzer.string_val(), r"a\nb\");
+}
+
+#[test]
+fn string_escapes() {
+    assert_eq!(lex_escapes(r#""a\nb" "\t" "\\" "say \"hi\"" "\\n""#), vec![
+        ok(StringConst, "a\nb"),
+        ok(StringConst, "\t"),
+        ok(StringConst, "\\"),
+        ok(StringConst, "say \"hi\""),
+        ok(StringConst, "\\n"),
+    ]);
+}
+
+#[test]
+fn unknown_string_escapes() {
+    assert_eq!(lex_escapes(r#""\q" "\0" "a\"#), vec![
+        Err(E_UNKNOWN_ESCAPE),
+        Err(E_UNKNOWN_ESCAPE),
+        Err(E_UNTERMINATED_STRING),
+    ]);
+    // Raw tabs are still not allowed
+    assert_eq!(lex_escapes("\"a\tb\""), vec![Err(E_UNSUPPORTED_CHAR)]);
+}
+
+#[test]
+fn line_comments() {
+    assert_eq!(kinds("x // comment ; \"\ny"), vec![Identifier, Identifier]);
+    assert_eq!(kinds("x // at the end of the file"), vec![Identifier]);
+    assert_eq!(kinds("//\n//\nx"), vec![Identifier]);
+}
+
+#[test]
+fn block_comments() {
+    assert_eq!(kinds("x /* a\n * b */ y"), vec![Identifier, Identifier]);
+    assert_eq!(kinds("x /** API\n  docs */ y"), vec![Identifier, Identifier]);
+    assert_eq!(kinds("/**/x/***/"), vec![Identifier]);
+    // Comments do not nest
+    assert_eq!(kinds("/* /* */ x */"), vec![Identifier, Symbol, Symbol]);
+}
+
+#[test]
+fn unterminated_block_comments() {
+    assert_eq!(lex("x /* no end"), vec![ok(Identifier, "x"), Err(E_UNTERMINATED_COMMENT)]);
+    assert_eq!(lex("/*/"), vec![Err(E_UNTERMINATED_COMMENT)]);
+    assert_eq!(lex("/* x *"), vec![Err(E_UNTERMINATED_COMMENT)]);
+}
+
+#[test]
+fn division_is_not_a_comment() {
+    assert_eq!(kinds("a/b / c"), vec![Identifier, Symbol, Identifier, Symbol, Identifier]);
+}
+
+#[test]
+fn whitespace() {
+    assert_eq!(kinds(" \tx\r\n\ny\x0c"), vec![Identifier, Identifier]);
+    assert_eq!(lex("x\u{a0}y"), vec![ok(Identifier, "x"), Err(E_UNEXPECTED_CHAR), ok(Identifier, "y")]);
+}
+
+#[test]
+fn unexpected_characters() {
+    for c in "@#$%^?!:'`\\".chars() {
+        assert_eq!(lex(&format!("x {} y", c)),
+                   vec![ok(Identifier, "x"), Err(E_UNEXPECTED_CHAR), ok(Identifier, "y")],
+                   "character {}", c);
+    }
+}
+
+#[test]
+fn empty_files() {
+    assert_eq!(lex(""), vec![]);
+    assert_eq!(lex("  // nothing\n/* here */ "), vec![]);
+
+    let mut analyzer = JackAnalyzer::from_source("Test.jack", "");
+    assert_eq!(analyzer.advance().unwrap_err().code, E_UNEXPECTED_EOF);
+    assert!(analyzer.at_eof());
+}
+
+#[test]
+fn spans() {
+    let mut analyzer = JackAnalyzer::from_source("Test.jack", "/* a\nb */ let\n\t/* é */ \"x\" y");
+    analyzer.advance().unwrap();
+    let span = analyzer.span();
+    assert_eq!((span.start.line, span.start.column, span.start.offset), (2, 6, 10));
+    assert_eq!((span.end.line, span.end.column, span.end.offset), (2, 9, 13));
+
+    analyzer.advance().unwrap();
+    let span = analyzer.span();
+    assert_eq!((span.start.line, span.start.column), (3, 10));
+    // Columns count characters and offsets count bytes
+    assert_eq!((span.end.column, span.end.offset), (13, 27));
+
+    analyzer.advance().unwrap();
+    assert_eq!(analyzer.span().start.column, 14);
+}
+
+#[test]
+fn lookahead() {
+    let mut analyzer = JackAnalyzer::from_source("Test.jack", "do f(x);");
+    assert_eq!(analyzer.peek(1).unwrap().text, "do");
+    analyzer.advance().unwrap();
+    assert_eq!(analyzer.peek(0).unwrap().text, "do");
+    assert_eq!(analyzer.peek(2).unwrap().text, "(");
+    assert!(analyzer.peek(6).is_none());
+}
+
+#[test]
+fn errors_do_not_stop_the_lexer() {
+    assert_eq!(lex("let x = 99999; @ \"abc\nlet _y = 1;"), vec![
+        ok(Keyword, "let"),
+        ok(Identifier, "x"),
+        ok(Symbol, "="),
+        Err(E_INT_RANGE),
+        ok(Symbol, ";"),
+        Err(E_UNEXPECTED_CHAR),
+        Err(E_UNTERMINATED_STRING),
+        ok(Keyword, "let"),
+        ok(Identifier, "_y"),
+        ok(Symbol, "="),
+        ok(IntConst, "1"),
+        ok(Symbol, ";"),
+    ]);
+}
+
+#[test]
+fn underscores_compile() {
+    let output = jackcompiler::compile_source("Main.jack", "
+        class Main {
+            function void main() {
+                var int my_var;
+                let my_var = 32767;
+                return;
+            }
+        }").unwrap();
+    assert!(output.code.contains("push constant 32767\npop local 0\n"));
+}
+
+#[test]
+fn escapes_compile_to_hack_codes() {
+    let source = r#"
+        class Main {
+            function void main() {
+                do Output.printString("a\n\t\\\"");
+                return;
+            }
+        }"#;
+    let options = jackcompiler::CompileOptions { string_escapes: true, ..Default::default() };
+    let output = jackcompiler::compile_source_with("Main.jack", source, &options).unwrap();
+    let codes: Vec<&str> = output.code.lines()
+        .filter(|line| line.starts_with("push constant"))
+        .collect();
+    assert_eq!(codes, vec![
+        "push constant 5",
+        "push constant 97",
+        "push constant 128",
+        "push constant 32",
+        "push constant 92",
+        "push constant 34",
+        // The return value of main
+        "push constant 0",
+    ]);
+
+    assert!(jackcompiler::compile_source("Main.jack", source).is_err());
+}
+
+#[test]
+fn unterminated_comments_are_reported() {
+    let errors = jackcompiler::compile_source("Main.jack", "
+        class Main {
+            function void main() {
+                return;
+            }
+        /* }").unwrap_err().errors;
+    assert!(errors.iter().any(|error| error.code == E_UNTERMINATED_COMMENT));
+}
@@ -0,0 +1,108 @@
+// Tests for the signatures of the Jack OS and for projects that supply their
+// own OS
+
+extern crate jackcompiler;
+
+mod common;
+
+use common::compile_project;
+use jackcompiler::ast::SubroutineKind;
+use jackcompiler::class_index::ClassIndex;
+use jackcompiler::error::*;
+use jackcompiler::os;
+use jackcompiler::CompileOptions;
+
+#[test]
+fn every_os_class_is_described() {
+    let mut names: Vec<String> = os::classes().into_iter().map(|class| class.name).collect();
+    names.sort();
+    assert_eq!(names, vec!["Array", "Keyboard", "Math", "Memory", "Output", "Screen", "String", "Sys"]);
+}
+
+#[test]
+fn os_signatures() {
+    let index = ClassIndex::with_os();
+
+    let multiply = index.subroutine("Math", "multiply").unwrap();
+    assert_eq!(multiply.kind, SubroutineKind::Function);
+    assert_eq!(multiply.return_type, Some("int".to_string()));
+    let types: Vec<&str> = multiply.parameters.iter().map(|parameter| &*parameter.type_name).collect();
+    assert_eq!(types, vec!["int", "int"]);
+
+    let append_char = index.subroutine("String", "appendChar").unwrap();
+    assert_eq!(append_char.kind, SubroutineKind::Method);
+    assert_eq!(append_char.return_type, Some("String".to_string()));
+    assert_eq!(index.subroutine("String", "new").unwrap().kind, SubroutineKind::Constructor);
+    assert_eq!(index.subroutine("Sys", "halt").unwrap().return_type, None);
+
+    // Nothing points into a source of the project
+    assert!(index.classes().all(|class| class.span.is_none()));
+    assert!(multiply.span.is_none());
+}
+
+#[test]
+fn calls_to_the_os_are_checked() {
+    let main = "
+        class Main {
+            function void main() {
+                var String s;
+                let s = \"hi\";
+                do Output.printString(s);
+                do Output.printInteger(s.length() * 2);
+                do s.reverse();
+                do Sys.halt();
+                return;
+            }
+        }";
+    assert_eq!(compile_project(&[("Main.jack", main)]), Err(vec![E_UNDEFINED_SUBROUTINE, E_UNDEFINED_SUBROUTINE]));
+}
+
+#[test]
+fn projects_can_supply_their_own_os() {
+    let math = "
+        class Math {
+            function int multiply(int x, int y) { return 0; }
+            function int cube(int x) { return x * x * x; }
+        }";
+    let main = "
+        class Main {
+            function void main() {
+                do Output.printInt(Math.cube(3));
+                return;
+            }
+        }";
+    let code = compile_project(&[("Main.jack", main), ("Math.jack", math)]).unwrap();
+    assert!(code[0].contains("call Math.cube 1\n"));
+
+    // The supplied class replaces the one of the OS completely
+    let main = "
+        class Main {
+            function void main() {
+                do Output.printInt(Math.sqrt(9) / 3);
+                return;
+            }
+        }";
+    assert_eq!(compile_project(&[("Main.jack", main), ("Math.jack", math)]),
+               Err(vec![E_UNDEFINED_SUBROUTINE]));
+}
+
+#[test]
+fn operators_need_the_os() {
+    let string = "
+        class String {
+            constructor String new(int maxLength) { return this; }
+        }";
+    let main = "class Main { function void main() { do Output.printString(\"hi\"); return; } }";
+    let diagnostics = jackcompiler::compile_project(&[("Main.jack", main), ("String.jack", string)],
+                                                    &CompileOptions::default()).unwrap_err();
+    let error = &diagnostics.errors[0];
+    assert_eq!(error.code, E_UNDEFINED_SUBROUTINE);
+    assert_eq!(error.message, "Class String has no subroutine named appendChar");
+    assert_eq!(error.help, vec!["a string constant is compiled to a call to String.appendChar"]);
+}
+
+#[test]
+fn an_os_class_supplied_twice_is_an_error() {
+    let math = "class Math { function int multiply(int x, int y) { return 0; } }";
+    assert_eq!(compile_project(&[("Math.jack", math), ("Math2.jack", math)]), Err(vec![E_DUPLICATE_DEFINITION]));
+}
@@ -0,0 +1,98 @@
+// Tests for how the parser recovers from syntax errors
+
+extern crate jackcompiler;
+
+use jackcompiler::jack_analyzer::JackAnalyzer;
+use jackcompiler::parser::Parser;
+
+// The line and message of every error in a source
+fn syntax_errors(source: &str) -> Vec<(usize, String)> {
+    let errors = Parser::new(JackAnalyzer::from_source("Main.jack", source)).parse_class().unwrap_err();
+    errors.into_iter().map(|error| (error.span.unwrap().start.line, error.message)).collect()
+}
+
+#[test]
+fn errors_in_every_statement() {
+    let source = "class Main {
+    function void main() {
+        let x = ;
+        do Output.printInt(1;
+        let y = 2;
+        return
+    }
+}";
+    let lines: Vec<usize> = syntax_errors(source).into_iter().map(|(line, _)| line).collect();
+    assert_eq!(lines, vec![3, 4, 7]);
+}
+
+#[test]
+fn errors_before_a_subroutine() {
+    // The error is found at the keyword of the method, which must still be
+    // parsed
+    let source = "class Main {
+    field int x
+    method void f() {
+        let x = ;
+        do g(;
+        return;
+    }
+}";
+    let errors = syntax_errors(source);
+    assert_eq!(errors[0], (3, "Expected ; at end of variable declaration".to_string()));
+    let lines: Vec<usize> = errors.iter().map(|&(line, _)| line).collect();
+    assert_eq!(lines, vec![3, 4, 5]);
+}
+
+#[test]
+fn errors_in_class_variables() {
+    let source = "class Main {
+    field int x y;
+    field boolean;
+    static char c
+    field Array a;
+    function void main() {
+        let a = ;
+        return;
+    }
+}";
+    let lines: Vec<usize> = syntax_errors(source).into_iter().map(|(line, _)| line).collect();
+    assert_eq!(lines, vec![2, 3, 5, 7]);
+}
+
+#[test]
+fn errors_in_subroutine_declarations() {
+    let source = "class Main {
+    function void (int a) {
+        let a = ;
+        return;
+    }
+    method int f(int) {
+        return 0;
+    }
+    function void g() {
+        let b = ;
+        return;
+    }
+}";
+    // The bodies of subroutines with a broken declaration are skipped
+    let lines: Vec<usize> = syntax_errors(source).into_iter().map(|(line, _)| line).collect();
+    assert_eq!(lines, vec![2, 6, 10]);
+}
+
+#[test]
+fn tokens_after_the_class() {
+    let source = "class A {
+    function void f() { return; }
+}
+class B {
+    function void g() { return; }
+}";
+    assert_eq!(syntax_errors(source), vec![(4, "Unexpected tokens after the end of class A".to_string())]);
+
+    let source = "class A { function void f() { return; } } }}} let x = ;";
+    assert_eq!(syntax_errors(source), vec![(1, "Unexpected tokens after the end of class A".to_string())]);
+
+    // Comments after the class are fine
+    let source = "class A { function void f() { return; } }\n// The end\n";
+    assert!(Parser::new(JackAnalyzer::from_source("A.jack", source)).parse_class().is_ok());
+}
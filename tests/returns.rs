@@ -0,0 +1,62 @@
+// Tests for the checks on return statements and on the paths through a
+// subroutine
+
+extern crate jackcompiler;
+
+mod common;
+
+use common::compile;
+use jackcompiler::error::*;
+
+// Compile a single subroutine in a class with a field
+fn subroutine(source: &str) -> Result<String, Vec<&'static str>> {
+    compile(&format!("class Main {{ field int x; {} }}", source))
+}
+
+#[test]
+fn every_path_returns() {
+    assert!(subroutine("function int f(int a) { if (a < 0) { return -a; } else { return a; } }").is_ok());
+    assert!(subroutine("function int f(int a) { while (a > 0) { let a = a - 1; } return a; }").is_ok());
+    assert!(subroutine("function void f() { while (true) { do Output.println(); } }").is_ok());
+    assert!(subroutine("function void f(boolean a) { if (a) { return; } else { if (~a) { return; } else { return; } } }")
+            .is_ok());
+}
+
+#[test]
+fn missing_returns() {
+    assert_eq!(subroutine("function void f() { do Output.println(); }"), Err(vec![E_MISSING_RETURN]));
+    assert_eq!(subroutine("function int f(int a) { if (a < 0) { return -a; } }"), Err(vec![E_MISSING_RETURN]));
+    assert_eq!(subroutine("function int f(int a) { while (a > 0) { return a; } }"), Err(vec![E_MISSING_RETURN]));
+    assert_eq!(subroutine("function int f(boolean a) { if (a) { return 1; } else { let a = false; } }"),
+               Err(vec![E_MISSING_RETURN]));
+}
+
+#[test]
+fn missing_returns_point_at_the_subroutine() {
+    let source = "class Main {\n    field int x;\n    method int size() {\n        let x = 1;\n    }\n}";
+    let diagnostics = jackcompiler::compile_source("Main.jack", source).unwrap_err();
+    let error = &diagnostics.errors[0];
+    assert_eq!(error.message, "Main.size can reach its end without returning");
+    assert_eq!(error.span.as_ref().unwrap().start.line, 3);
+    assert_eq!(error.help, vec!["add a return statement with a value at the end"]);
+}
+
+#[test]
+fn returns_that_do_not_match_the_return_type() {
+    assert_eq!(subroutine("function void f() { return 1; }"), Err(vec![E_RETURN_MISMATCH]));
+    assert_eq!(subroutine("method int f() { if (x > 0) { return; } return x; }"), Err(vec![E_RETURN_MISMATCH]));
+
+    let diagnostics = jackcompiler::compile_source("Main.jack", "class Main { function char f() { return; } }")
+        .unwrap_err();
+    assert_eq!(diagnostics.errors[0].message, "Main.f returns char but this return has no value");
+}
+
+#[test]
+fn constructors_return_this() {
+    assert!(subroutine("constructor Main new() { let x = 0; return this; }").is_ok());
+    assert_eq!(subroutine("constructor Main new() { return null; }"), Err(vec![E_CONSTRUCTOR_RETURN]));
+    assert_eq!(subroutine("constructor Main new() { return Main.create(); } function Main create() { return null; }"),
+               Err(vec![E_CONSTRUCTOR_RETURN]));
+    assert_eq!(subroutine("constructor Main new() { return; }"), Err(vec![E_RETURN_MISMATCH]));
+    assert_eq!(subroutine("constructor Main new() { let x = 0; }"), Err(vec![E_MISSING_RETURN]));
+}
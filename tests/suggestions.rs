@@ -0,0 +1,131 @@
+// Tests for the errors about undefined names and the names they suggest
+
+extern crate jackcompiler;
+
+use jackcompiler::error::*;
+use jackcompiler::suggest::{closest, edit_distance};
+use jackcompiler::{CompileOptions, Diagnostics, VmOutput};
+
+// Classes other than Main and those of the OS are undefined
+fn whole_program(source: &str) -> Result<VmOutput, Diagnostics> {
+    let options = CompileOptions { whole_program: true, ..CompileOptions::default() };
+    jackcompiler::compile_source_with("Main.jack", source, &options)
+}
+
+// The code and help messages of the first error
+fn first_error(source: &str) -> (&'static str, Vec<String>) {
+    let diagnostics = whole_program(source).unwrap_err();
+    let error = &diagnostics.errors[0];
+    (error.code, error.help.clone())
+}
+
+#[test]
+fn edit_distances() {
+    assert_eq!(edit_distance("counter", "counter"), 0);
+    assert_eq!(edit_distance("counter", "coutner"), 2);
+    assert_eq!(edit_distance("count", "counter"), 2);
+    assert_eq!(edit_distance("", "abc"), 3);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("größe", "grosse"), 3);
+}
+
+#[test]
+fn closest_names() {
+    let names = ["counter", "count", "total", "x"];
+    assert_eq!(closest("countet", names.iter().cloned()), Some("counter"));
+    // Ties go to the first name alphabetically
+    assert_eq!(closest("countr", names.iter().cloned()), Some("count"));
+    assert_eq!(closest("cont", names.iter().cloned()), Some("count"));
+    assert_eq!(closest("y", names.iter().cloned()), Some("x"));
+    assert_eq!(closest("sum", names.iter().cloned()), None);
+    // A name is not suggested for itself
+    assert_eq!(closest("total", names.iter().cloned()), None);
+}
+
+#[test]
+fn undefined_variables() {
+    let source = "
+        class Main {
+            field int counter;
+            method void inc() {
+                let countr = counter + 1;
+                return;
+            }
+        }";
+    assert_eq!(first_error(source), (E_UNDEFINED_VARIABLE, vec!["did you mean `counter`?".to_string()]));
+
+    // Without a close name the help says how to declare it
+    let source = "class Main { function void main() { let total = 0; return; } }";
+    assert_eq!(first_error(source).1,
+               vec!["variables must be declared with var, static or field before they are used"]);
+}
+
+#[test]
+fn undefined_subroutines() {
+    let source = "
+        class Main {
+            function void main() {
+                do Output.printStrng(\"hi\");
+                return;
+            }
+        }";
+    assert_eq!(first_error(source), (E_UNDEFINED_SUBROUTINE, vec!["did you mean `printString`?".to_string()]));
+
+    let source = "
+        class Main {
+            function void main() {
+                do Main.runn();
+                return;
+            }
+            function void run() { return; }
+        }";
+    assert_eq!(first_error(source).1, vec!["did you mean `run`?"]);
+}
+
+#[test]
+fn undefined_classes() {
+    let source = "
+        class Main {
+            function void main() {
+                do Ouptut.println();
+                return;
+            }
+        }";
+    assert_eq!(first_error(source), (E_UNDEFINED_CLASS, vec!["did you mean `Output`?".to_string()]));
+
+    // Variables are suggested for receivers too
+    let source = "
+        class Main {
+            function void main() {
+                var String name;
+                do nam.length();
+                return;
+            }
+        }";
+    assert_eq!(first_error(source), (E_UNDEFINED_CLASS, vec!["did you mean `name`?".to_string()]));
+}
+
+#[test]
+fn undefined_types() {
+    let source = "
+        class Main {
+            field Strng name;
+            function Aray make(Game game) {
+                var Sting s;
+                return null;
+            }
+        }";
+    let diagnostics = whole_program(source).unwrap_err();
+    let errors: Vec<(&str, &str)> = diagnostics.errors.iter()
+        .map(|error| (&*error.message, error.help.first().map_or("", |help| &**help)))
+        .collect();
+    assert_eq!(errors, vec![
+        ("Undefined class Strng", "did you mean `String`?"),
+        ("Undefined class Aray", "did you mean `Array`?"),
+        ("Undefined class Game", ""),
+        ("Undefined class Sting", "did you mean `String`?"),
+    ]);
+
+    // Otherwise the classes are assumed to be compiled separately
+    assert!(jackcompiler::compile_source("Main.jack", source).is_ok());
+}
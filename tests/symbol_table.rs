@@ -0,0 +1,158 @@
+// Tests for the scopes of the symbol table and the errors for variables
+// defined twice
+
+extern crate jackcompiler;
+
+mod common;
+
+use common::compile;
+use jackcompiler::error::*;
+use jackcompiler::symbol_table::{Kind, SymbolTable};
+
+#[test]
+fn indices_are_counted_per_kind() {
+    let mut table = SymbolTable::new();
+    assert_eq!(table.define("a", "int", Kind::Static, None), Ok(0));
+    assert_eq!(table.define("b", "int", Kind::Field, None), Ok(0));
+    assert_eq!(table.define("c", "int", Kind::Static, None), Ok(1));
+    assert_eq!(table.define("d", "Array", Kind::Arg, None), Ok(0));
+    assert_eq!(table.define("e", "char", Kind::Var, None), Ok(0));
+    assert_eq!(table.define("f", "char", Kind::Var, None), Ok(1));
+
+    assert_eq!(table.var_count(Kind::Static), 2);
+    assert_eq!(table.var_count(Kind::Var), 2);
+    let symbol = table.get("d").unwrap();
+    assert_eq!((symbol.kind, symbol.index, &*symbol.type_name), (Kind::Arg, 0, "Array"));
+}
+
+#[test]
+fn subroutine_scope_is_cleared() {
+    let mut table = SymbolTable::new();
+    table.define("field", "int", Kind::Field, None).unwrap();
+    table.define("local", "int", Kind::Var, None).unwrap();
+    table.define("arg", "int", Kind::Arg, None).unwrap();
+
+    table.start_subroutine();
+    assert_eq!(table.kind_of("local"), Kind::None);
+    assert_eq!(table.kind_of("arg"), Kind::None);
+    assert_eq!(table.kind_of("field"), Kind::Field);
+    assert_eq!(table.var_count(Kind::Var), 0);
+    assert_eq!(table.type_of("local"), None);
+    assert_eq!(table.index_of("local"), None);
+}
+
+#[test]
+fn locals_hide_fields() {
+    let mut table = SymbolTable::new();
+    table.define("x", "int", Kind::Field, None).unwrap();
+    table.define("y", "int", Kind::Field, None).unwrap();
+    table.start_subroutine();
+    table.define("x", "boolean", Kind::Var, None).unwrap();
+
+    assert_eq!(table.kind_of("x"), Kind::Var);
+    assert_eq!(table.type_of("x"), Some("boolean".to_string()));
+    assert_eq!(table.index_of("y"), Some(1));
+
+    table.start_subroutine();
+    assert_eq!(table.kind_of("x"), Kind::Field);
+}
+
+#[test]
+fn duplicates_are_rejected() {
+    let mut table = SymbolTable::new();
+    table.define("x", "int", Kind::Static, None).unwrap();
+    let previous = table.define("x", "char", Kind::Field, None).unwrap_err();
+    assert_eq!(previous.kind, Kind::Static);
+    assert_eq!(table.var_count(Kind::Field), 0);
+
+    table.define("a", "int", Kind::Arg, None).unwrap();
+    assert!(table.define("a", "int", Kind::Var, None).is_err());
+    assert_eq!(table.type_of("a"), Some("int".to_string()));
+}
+
+#[test]
+fn locals_do_not_leak_into_later_subroutines() {
+    let source = "
+        class Main {
+            function void f() {
+                var int x;
+                let x = 1;
+                return;
+            }
+            function void g() {
+                let x = 2;
+                return;
+            }
+        }";
+    assert_eq!(compile(source), Err(vec![E_UNDEFINED_VARIABLE]));
+}
+
+#[test]
+fn locals_with_the_name_of_a_field() {
+    let source = "
+        class Main {
+            field int x, y;
+            method void f() {
+                var int y;
+                let y = x;
+                return;
+            }
+            method void g() {
+                let y = 3;
+                return;
+            }
+        }";
+    let code = compile(source).unwrap();
+    assert!(code.contains("push this 0\npop local 0\n"));
+    assert!(code.contains("push constant 3\npop this 1\n"));
+}
+
+#[test]
+fn fields_in_functions() {
+    let source = "
+        class T {
+            field int a;
+            static int b;
+            function int f() {
+                let a = 3;
+                let b = a;
+                return a;
+            }
+            function T g() { return this; }
+            method int h() { return a; }
+        }";
+    assert_eq!(compile(source), Err(vec![E_THIS_IN_FUNCTION; 4]));
+
+    // The error points at the declaration of the field
+    let source = "class T {\n    field int a;\n    function void f() {\n        let a = 1;\n        return;\n    }\n}";
+    let diagnostics = jackcompiler::compile_source("T.jack", source).unwrap_err();
+    let error = &diagnostics.errors[0];
+    assert_eq!(error.message, "Cannot use field a in function T.f");
+    assert_eq!(error.span.as_ref().unwrap().start.line, 4);
+    assert_eq!(error.notes[0].span.as_ref().unwrap().start.line, 2);
+}
+
+#[test]
+fn duplicate_definitions() {
+    let fields = "class Main { field int x; static boolean x; }";
+    assert_eq!(compile(fields), Err(vec![E_DUPLICATE_DEFINITION]));
+
+    let locals = "
+        class Main {
+            function void f(int a, int b) {
+                var int a;
+                var char c, c;
+                return;
+            }
+        }";
+    assert_eq!(compile(locals), Err(vec![E_DUPLICATE_DEFINITION, E_DUPLICATE_DEFINITION]));
+}
+
+#[test]
+fn duplicate_definitions_point_at_the_first() {
+    let source = "class Main {\n    field int x;\n    field int x;\n}";
+    let diagnostics = jackcompiler::compile_source("Main.jack", source).unwrap_err();
+    let error = &diagnostics.errors[0];
+    assert_eq!(error.span.as_ref().unwrap().start.line, 3);
+    assert_eq!(error.notes[0].span.as_ref().unwrap().start.line, 2);
+}
@@ -0,0 +1,109 @@
+// Tests for the whitespace and comments kept on tokens
+
+extern crate jackcompiler;
+
+use jackcompiler::jack_analyzer::{JackAnalyzer, TriviaKind};
+
+const SOURCE: &str = "// Main.jack
+/** The entry point. */
+class Main {
+    field int x; // the x coordinate
+
+    /**
+     * Runs the program.
+     */
+    function void main() {
+        /* a block comment */ do Output.printInt(1 /* inline */ + 2);
+\t\treturn;\r
+    }
+}
+// trailing comment";
+
+// Put the source back together from the tokens and their trivia
+fn round_trip(source: &str) -> String {
+    let analyzer = JackAnalyzer::from_source("Main.jack", source);
+    let mut text = String::new();
+    for token in analyzer.tokens() {
+        for trivia in &token.leading {
+            text += &trivia.text;
+        }
+        text += &token.text;
+    }
+    for trivia in analyzer.end_trivia() {
+        text += &trivia.text;
+    }
+    text
+}
+
+#[test]
+fn sources_round_trip() {
+    assert_eq!(round_trip(SOURCE), SOURCE);
+    assert_eq!(round_trip(""), "");
+    assert_eq!(round_trip("  \n// only trivia"), "  \n// only trivia");
+}
+
+#[test]
+fn invalid_sources_round_trip() {
+    for source in &["class @ Main { \"unterminated\n }", "let x = 99999;", "x /* unterminated", "\"é\" größe"] {
+        assert_eq!(&round_trip(source), source);
+    }
+}
+
+#[test]
+fn trivia_kinds() {
+    let analyzer = JackAnalyzer::from_source("Main.jack", SOURCE);
+    let class = &analyzer.tokens()[0];
+    let kinds: Vec<TriviaKind> = class.leading.iter().map(|trivia| trivia.kind).collect();
+    assert_eq!(kinds, vec![
+        TriviaKind::LineComment,
+        TriviaKind::Whitespace,
+        TriviaKind::DocComment,
+        TriviaKind::Whitespace,
+    ]);
+    assert_eq!(class.leading[0].text, "// Main.jack");
+
+    let end: Vec<TriviaKind> = analyzer.end_trivia().iter().map(|trivia| trivia.kind).collect();
+    assert_eq!(end, vec![TriviaKind::Whitespace, TriviaKind::LineComment]);
+}
+
+#[test]
+fn empty_block_comments_are_not_doc_comments() {
+    let analyzer = JackAnalyzer::from_source("Main.jack", "/**/ x /***/ y");
+    assert_eq!(analyzer.tokens()[0].leading[0].kind, TriviaKind::BlockComment);
+    assert_eq!(analyzer.tokens()[1].leading[1].kind, TriviaKind::DocComment);
+}
+
+#[test]
+fn doc_comments() {
+    let analyzer = JackAnalyzer::from_source("Main.jack", SOURCE);
+    let tokens = analyzer.tokens();
+    assert_eq!(tokens[0].doc_comment(), Some("/** The entry point. */"));
+
+    let function = tokens.iter().find(|token| token.text == "function").unwrap();
+    assert_eq!(function.doc_comment(), Some("/**\n     * Runs the program.\n     */"));
+
+    let field = tokens.iter().find(|token| token.text == "field").unwrap();
+    assert_eq!(field.doc_comment(), None);
+
+    // Another comment in between hides the doc comment
+    let analyzer = JackAnalyzer::from_source("Main.jack", "/** docs */ // note\nclass");
+    assert_eq!(analyzer.tokens()[0].doc_comment(), None);
+}
+
+#[test]
+fn trivia_spans() {
+    let analyzer = JackAnalyzer::from_source("Main.jack", SOURCE);
+    let do_token = analyzer.tokens().iter().find(|token| token.text == "do").unwrap();
+    let comment = &do_token.leading[1];
+    assert_eq!(comment.text, "/* a block comment */");
+    assert_eq!((comment.span.start.line, comment.span.start.column), (10, 9));
+    assert_eq!(&SOURCE[comment.span.start.offset..comment.span.end.offset], comment.text);
+}
+
+#[test]
+fn trivia_does_not_change_the_output() {
+    let compact = "class Main{field int x;function void main(){do Output.printInt(1+2);return;}}";
+    let with_trivia = jackcompiler::compile_source("Main.jack", SOURCE).unwrap();
+    let without_trivia = jackcompiler::compile_source("Main.jack", compact).unwrap();
+    assert_eq!(with_trivia.code, without_trivia.code);
+}
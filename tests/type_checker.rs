@@ -0,0 +1,218 @@
+// Tests for the type checker and its strict mode
+
+extern crate jackcompiler;
+
+use jackcompiler::error::*;
+use jackcompiler::CompileOptions;
+
+fn options(strict_types: bool) -> CompileOptions {
+    CompileOptions { strict_types, ..Default::default() }
+}
+
+// Compile a class with the given variables and statements in its function
+// main in strict mode, returning the codes of the errors
+fn check(declarations: &str, statements: &str) -> Vec<&'static str> {
+    let source = format!("class Main {{ function void main() {{ {} {} return; }} }}", declarations, statements);
+    match jackcompiler::compile_source_with("Main.jack", &source, &options(true)) {
+        Ok(_) => Vec::new(),
+        Err(diagnostics) => diagnostics.errors.iter().map(|error| error.code).collect(),
+    }
+}
+
+// The codes of the warnings without strict mode, which must compile
+fn warnings(declarations: &str, statements: &str) -> Vec<&'static str> {
+    let source = format!("class Main {{ function void main() {{ {} {} return; }} }}", declarations, statements);
+    let output = jackcompiler::compile_source("Main.jack", &source).unwrap();
+    output.warnings.iter().map(|warning| warning.code).collect()
+}
+
+#[test]
+fn assignments() {
+    let declarations = "var int i; var char c; var boolean b; var String s; var Array a;";
+    assert!(check(declarations, "let i = 1; let c = 65; let b = true; let s = \"x\"; let s = null; let a = null;")
+            .is_empty());
+    assert_eq!(check(declarations, "let i = \"hello\";"), vec![E_TYPE_MISMATCH]);
+    assert_eq!(check(declarations, "let b = 0; let s = false;"), vec![E_TYPE_MISMATCH, E_TYPE_MISMATCH]);
+    assert_eq!(check(declarations, "let i = s.length() * 2; let c = s.charAt(0); let b = i < 2;"),
+               Vec::<&str>::new());
+}
+
+#[test]
+fn implicit_conversions() {
+    let declarations = "var int i; var char c; var String s; var Array a;";
+    let statements = "let i = c; let c = i + 1; let a = s; let s = a; let s = 2048;";
+    assert_eq!(check(declarations, statements), vec![E_TYPE_MISMATCH; 5]);
+    assert_eq!(warnings(declarations, statements), vec![E_TYPE_MISMATCH; 5]);
+
+    // Other mismatches are still errors without strict mode
+    let source = "class Main { function void main() { var int i; let i = true; return; } }";
+    let diagnostics = jackcompiler::compile_source("Main.jack", source).unwrap_err();
+    assert_eq!(diagnostics.errors[0].code, E_TYPE_MISMATCH);
+}
+
+#[test]
+fn arguments() {
+    let declarations = "var String s; var char c;";
+    assert!(check(declarations, "do Output.printString(s); do Output.printChar(c); do s.setCharAt(0, 65);")
+            .is_empty());
+    assert_eq!(check(declarations, "do Output.printString(1); do Output.printInt(s);"),
+               vec![E_TYPE_MISMATCH, E_TYPE_MISMATCH]);
+    assert_eq!(check(declarations, "do Output.printInt(c);"), vec![E_TYPE_MISMATCH]);
+    assert_eq!(warnings(declarations, "do Output.printInt(c); do Output.printString(2048);"),
+               vec![E_TYPE_MISMATCH, E_TYPE_MISMATCH]);
+
+    // The OS takes any object as an Array
+    assert!(check(declarations, "do Memory.deAlloc(s);").is_empty());
+    assert_eq!(check(declarations, "do Memory.deAlloc(c);"), vec![E_TYPE_MISMATCH]);
+}
+
+#[test]
+fn arguments_to_classes_in_the_project() {
+    let point = "
+        class Point {
+            field int x;
+            constructor Point new(int ax) { let x = ax; return this; }
+            method boolean equals(Point other) { return x = other.getX(); }
+            method int getX() { return x; }
+        }";
+    let main = "
+        class Main {
+            function void main() {
+                var Point p;
+                let p = Point.new(1);
+                do p.equals(p);
+                do p.equals(true);
+                return;
+            }
+        }";
+    let diagnostics = jackcompiler::compile_project(&[("Main.jack", main), ("Point.jack", point)],
+                                                    &CompileOptions::default()).unwrap_err();
+    let codes: Vec<&str> = diagnostics.errors.iter().map(|error| error.code).collect();
+    assert_eq!(codes, vec![E_TYPE_MISMATCH]);
+    assert_eq!(diagnostics.errors[0].message, "Passing boolean as other of Point.equals, which has type Point");
+    assert_eq!(diagnostics.errors[0].notes[0].span.as_ref().unwrap().file.as_ref(), "Point.jack");
+}
+
+#[test]
+fn operators() {
+    let declarations = "var int i; var boolean b; var char c;";
+    assert!(check(declarations, "let i = (i + 2) * -i; let b = (i < 3) & ~b; let i = i & 255 | ~i; let b = i = 2;")
+            .is_empty());
+    assert_eq!(check(declarations, "let i = b + 1;"), vec![E_INVALID_OPERAND]);
+    assert_eq!(check(declarations, "let b = b & 1;"), vec![E_INVALID_OPERAND]);
+    assert_eq!(check(declarations, "let b = b < i;"), vec![E_INVALID_OPERAND]);
+    assert_eq!(check(declarations, "let i = -b;"), vec![E_INVALID_OPERAND]);
+    assert_eq!(check(declarations, "let b = \"a\" = 1;"), vec![E_INVALID_OPERAND]);
+
+    // Constants can be used as chars, but arithmetic on chars converts them
+    assert!(check(declarations, "let b = c = 81;").is_empty());
+    assert_eq!(check(declarations, "let i = c - 48;"), vec![E_INVALID_OPERAND]);
+    assert_eq!(warnings(declarations, "let i = c - 48;"), vec![E_INVALID_OPERAND]);
+}
+
+#[test]
+fn indexing() {
+    let declarations = "var Array a; var int i; var boolean b; var String s;";
+    assert!(check(declarations, "let a[i] = a[i + 1]; let a[0] = true; let i = a[2] + 1; let s = a[3];")
+            .is_empty());
+    assert_eq!(check(declarations, "let i = i[0];"), vec![E_NOT_AN_ARRAY]);
+    assert_eq!(check(declarations, "let b[0] = 1;"), vec![E_NOT_AN_ARRAY]);
+    assert_eq!(check(declarations, "let i = a[b];"), vec![E_TYPE_MISMATCH]);
+    assert_eq!(warnings(declarations, "let s[1] = 0;"), vec![E_NOT_AN_ARRAY]);
+}
+
+#[test]
+fn return_values() {
+    let source = "
+        class Main {
+            function int f() { return 1; }
+            function String g() { return null; }
+            function char h() { return true; }
+            method Main i() { return this; }
+            function Array j() { return \"text\"; }
+        }";
+    let diagnostics = jackcompiler::compile_source_with("Main.jack", source, &options(true)).unwrap_err();
+    let messages: Vec<&str> = diagnostics.errors.iter().map(|error| &*error.message).collect();
+    assert_eq!(messages, vec!["Returning boolean from Main.h, which returns char",
+                              "Returning String from Main.j, which returns Array"]);
+    assert_eq!(jackcompiler::compile_source("Main.jack", source).unwrap_err().warnings.len(), 1);
+}
+
+#[test]
+fn void_values() {
+    assert_eq!(check("var int i;", "let i = Output.println();"), vec![E_VOID_VALUE]);
+    assert!(check("", "do Output.println();").is_empty());
+}
+
+#[test]
+fn undefined_names_are_only_reported_once() {
+    assert_eq!(check("var int i;", "let i = j + 1; let j = true;"),
+               vec![E_UNDEFINED_VARIABLE, E_UNDEFINED_VARIABLE]);
+}
+
+#[test]
+fn warnings_are_rendered() {
+    let source = "class Main {\n    function void main() {\n        var int i;\n        let i = null;\n        let i = true;\n        return;\n    }\n}";
+    let diagnostics = jackcompiler::compile_source("Main.jack", source).unwrap_err();
+    let rendered = diagnostics.render(false);
+    assert!(rendered.starts_with("semantic error[E0305]: Assigning boolean to i, which has type int\n"));
+    assert!(rendered.contains("semantic warning[E0305]: Assigning null to i, which has type int\n"));
+    assert!(rendered.contains("= help: Jack converts null to int implicitly\n"));
+    assert!(diagnostics.to_json().contains("{\"severity\":\"warning\","));
+}
+
+#[test]
+fn square_compiles_by_default() {
+    // Idioms from the Square program of project 11
+    let square = "
+        class Square {
+            field int x, y, size;
+            constructor Square new(int ax, int ay, int asize) {
+                let x = ax; let y = ay; let size = asize;
+                return this;
+            }
+            method void dispose() {
+                do Memory.deAlloc(this);
+                return;
+            }
+            method void incSize() {
+                if (((y + size) < 254) & ((x + size) < 510)) {
+                    let size = size + 2;
+                }
+                return;
+            }
+        }";
+    let game = "
+        class SquareGame {
+            field Square square;
+            field int direction;
+            constructor SquareGame new() {
+                let square = Square.new(0, 0, 30);
+                let direction = 0;
+                return this;
+            }
+            method void run() {
+                var char key;
+                var int digit;
+                var String s;
+                var boolean exit;
+                let exit = false;
+                let s = \"1\";
+                while (~exit) {
+                    while (key = 0) {
+                        let key = Keyboard.keyPressed();
+                    }
+                    if (key = 81) { let exit = true; }
+                    if (key = 90) { do square.incSize(); }
+                    if ((key > 47) & (key < 58)) { let direction = key - 48; }
+                    let digit = s.charAt(0);
+                }
+                do square.dispose();
+                return;
+            }
+        }";
+    let outputs = jackcompiler::compile_project(&[("Square.jack", square), ("SquareGame.jack", game)],
+                                                &CompileOptions::default()).unwrap();
+    assert!(outputs[0].warnings.is_empty());
+    assert!(!outputs[1].warnings.is_empty());
+}
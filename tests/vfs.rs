@@ -0,0 +1,98 @@
+// Tests for reading sources from and writing output to memory and zip
+// archives
+
+extern crate jackcompiler;
+extern crate zip;
+
+use jackcompiler::vfs::*;
+
+use std::io::prelude::*;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+fn paths(paths: &[&str]) -> Vec<PathBuf> {
+    paths.iter().map(PathBuf::from).collect()
+}
+
+// A zip archive with the given files, written through a ZipSink
+fn zip_archive(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut sink = ZipSink::new();
+    for &(path, contents) in files {
+        sink.create(Path::new(path)).unwrap().write_all(contents.as_bytes()).unwrap();
+    }
+    sink.finish(Cursor::new(Vec::new())).unwrap().into_inner()
+}
+
+#[test]
+fn memory_sources() {
+    let mut source = MemorySource::new();
+    source.add("proj/Main.jack", "class Main {}");
+    source.add("proj/sub/Game.jack", "class Game {}");
+    source.add("proj/notes.txt", "");
+    source.add("proj/Ball.jack", "class Ball {}");
+
+    assert!(source.is_dir(Path::new("proj")));
+    assert!(source.is_dir(Path::new("proj/sub")));
+    assert!(!source.is_dir(Path::new("proj/Main.jack")));
+    assert_eq!(source.jack_files(Path::new("proj"), false).unwrap(),
+               paths(&["proj/Ball.jack", "proj/Main.jack"]));
+    assert_eq!(source.jack_files(Path::new("proj"), true).unwrap(),
+               paths(&["proj/Ball.jack", "proj/Main.jack", "proj/sub/Game.jack"]));
+    assert_eq!(source.read(Path::new("proj/sub/Game.jack")).unwrap(), "class Game {}");
+    assert!(source.read(Path::new("proj/Game.jack")).is_err());
+}
+
+#[test]
+fn memory_sinks() {
+    let sink = MemorySink::new();
+    {
+        let mut writer = sink.clone();
+        let mut out = writer.create(Path::new("out/Main.vm")).unwrap();
+        out.write_all(b"function Main.main 0\n").unwrap();
+        out.write_all(b"return\n").unwrap();
+        writer.create(Path::new("out/Ball.vm")).unwrap();
+    }
+    assert_eq!(sink.paths(), paths(&["out/Ball.vm", "out/Main.vm"]));
+    assert_eq!(sink.get(Path::new("out/Main.vm")).unwrap(), b"function Main.main 0\nreturn\n");
+    assert_eq!(sink.get(Path::new("out/Ball.vm")).unwrap(), b"");
+
+    // Creating a file again truncates it
+    sink.clone().create(Path::new("out/Main.vm")).unwrap();
+    assert_eq!(sink.get(Path::new("out/Main.vm")).unwrap(), b"");
+}
+
+#[test]
+fn zip_round_trip() {
+    let archive = zip_archive(&[("Main.jack", "class Main {}"),
+                                ("game/Game.jack", "class Game {}"),
+                                ("game/README", "not a Jack file")]);
+    let source = ZipSource::from_reader(Path::new("proj"), Cursor::new(archive)).unwrap();
+
+    assert!(source.is_dir(Path::new("proj")));
+    assert!(source.is_dir(Path::new("proj/game")));
+    assert_eq!(source.jack_files(Path::new("proj"), false).unwrap(), paths(&["proj/Main.jack"]));
+    assert_eq!(source.jack_files(Path::new("proj"), true).unwrap(),
+               paths(&["proj/Main.jack", "proj/game/Game.jack"]));
+    assert_eq!(source.read(Path::new("proj/game/Game.jack")).unwrap(), "class Game {}");
+    assert!(source.read(Path::new("proj/game/README")).is_err());
+}
+
+#[test]
+fn zip_entries_outside_the_archive_are_skipped() {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for &(name, contents) in &[("../Evil.jack", "class Evil {}"), ("Main.jack", "class Main {}"),
+                               ("/etc/Abs.jack", "class Abs {}")] {
+        writer.start_file(name, FileOptions::default()).unwrap();
+        writer.write_all(contents.as_bytes()).unwrap();
+    }
+    let archive = writer.finish().unwrap().into_inner();
+
+    let source = ZipSource::from_reader(Path::new("proj"), Cursor::new(archive)).unwrap();
+    assert_eq!(source.jack_files(Path::new("proj"), true).unwrap(), paths(&["proj/Main.jack"]));
+    assert!(source.read(Path::new("Evil.jack")).is_err());
+    assert!(source.read(Path::new("proj/../Evil.jack")).is_err());
+    assert!(source.read(Path::new("/etc/Abs.jack")).is_err());
+}
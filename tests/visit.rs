@@ -0,0 +1,152 @@
+// Tests for the order and coverage of the default walks of Visitor and
+// MutVisitor
+
+extern crate jackcompiler;
+
+use jackcompiler::ast::*;
+use jackcompiler::compilation_engine::CompilationEngine;
+use jackcompiler::jack_analyzer::JackAnalyzer;
+use jackcompiler::parser::Parser;
+use jackcompiler::visit::*;
+
+const SOURCE: &str = "
+    class Main {
+        field Array items;
+        static int count, total;
+
+        method int get(int i, Main other) {
+            var char c;
+            let items[i] = other.get(-i, this);
+            if (c < 2) {
+                do Main.log(items[(i + 1)]);
+            } else {
+                while (~count) {
+                    let total = total + size();
+                }
+            }
+            return i;
+        }
+    }";
+
+fn parse(source: &str) -> Class {
+    Parser::new(JackAnalyzer::from_source("Main.jack", source)).parse_class().unwrap()
+}
+
+fn compile(class: &Class) -> String {
+    let mut compiler = CompilationEngine::from_writer("Main.vm", Vec::new());
+    compiler.compile_class(class).unwrap();
+    String::from_utf8(compiler.into_inner()).unwrap()
+}
+
+// Collects every name in the order it is visited
+#[derive(Default)]
+struct Names(Vec<String>);
+
+impl Visitor for Names {
+    fn visit_ident(&mut self, ident: &Ident) {
+        self.0.push(ident.name.clone());
+    }
+}
+
+// Counts calls and the statements they are in, going into the arguments too
+#[derive(Default)]
+struct Calls {
+    calls: usize,
+    statements: usize,
+}
+
+impl Visitor for Calls {
+    fn visit_statement(&mut self, statement: &Statement) {
+        self.statements += 1;
+        walk_statement(self, statement);
+    }
+
+    fn visit_subroutine_call(&mut self, call: &SubroutineCall) {
+        self.calls += 1;
+        walk_subroutine_call(self, call);
+    }
+}
+
+#[test]
+fn names_are_visited_in_source_order() {
+    let mut names = Names::default();
+    names.visit_class(&parse(SOURCE));
+    assert_eq!(names.0, vec!["Main", "Array", "items", "count", "total",
+                             "get", "i", "Main", "other", "c",
+                             "items", "i", "other", "get", "i",
+                             "c", "Main", "log", "items", "i",
+                             "count", "total", "total", "size", "i"]);
+}
+
+#[test]
+fn calls_and_statements_are_counted() {
+    let mut calls = Calls::default();
+    calls.visit_class(&parse(SOURCE));
+    assert_eq!((calls.calls, calls.statements), (3, 6));
+}
+
+// Removes every call to Main.log, in nested blocks too
+struct RemoveLogging;
+
+impl MutVisitor for RemoveLogging {
+    fn visit_statements_mut(&mut self, statements: &mut Vec<Statement>) {
+        statements.retain(|statement| match statement.kind {
+            StatementKind::Do(ref call) => {
+                !(call.receiver.as_ref().is_some_and(|receiver| receiver.name == "Main") && call.name.name == "log")
+            }
+            _ => true,
+        });
+        walk_statements_mut(self, statements);
+    }
+}
+
+// Replaces parentheses around a single term with the term
+struct RemoveParens;
+
+impl MutVisitor for RemoveParens {
+    fn visit_term_mut(&mut self, term: &mut Term) {
+        walk_term_mut(self, term);
+        let inner = match term.kind {
+            TermKind::Paren(ref expression) if expression.rest.is_empty() => expression.first.clone(),
+            _ => return,
+        };
+        *term = inner;
+    }
+}
+
+#[test]
+fn statements_are_removed() {
+    let source = "
+        class Main {
+            function void main(int x) {
+                do Main.log(x);
+                while (x > 0) {
+                    if (x = 1) {
+                        do Main.log(1);
+                    }
+                    let x = x - 1;
+                }
+                return;
+            }
+            function void log(int x) { return; }
+        }";
+    let mut class = parse(source);
+    RemoveLogging.visit_class_mut(&mut class);
+    let mut calls = Calls::default();
+    calls.visit_class(&class);
+    assert_eq!(calls.calls, 0);
+    assert!(!compile(&class).contains("call Main.log"));
+}
+
+#[test]
+fn terms_are_rewritten() {
+    let mut class = parse("class Main { function int f(int x) { return ((x)) + (-(1)) * (x + 1); } }");
+    RemoveParens.visit_class_mut(&mut class);
+    // The spans are those of the terms inside the parentheses, so compare
+    // the code instead of the trees
+    assert_eq!(compile(&class), compile(&parse("class Main { function int f(int x) { return x + -1 * (x + 1); } }")));
+    match class.subroutines[0].body[0].kind {
+        StatementKind::Return(Some(ref value)) => assert!(matches!(value.first.kind, TermKind::Var(_))),
+        _ => panic!("expected a return statement"),
+    }
+}
@@ -0,0 +1,42 @@
+// Tests that the generated VM code matches that of the original compiler,
+// and that errors do not stop the rest of the tree from being checked
+
+extern crate jackcompiler;
+
+use jackcompiler::compilation_engine::CompilationEngine;
+use jackcompiler::error::*;
+use jackcompiler::jack_analyzer::JackAnalyzer;
+use jackcompiler::parser::Parser;
+
+#[test]
+fn square() {
+    let class = Parser::new(JackAnalyzer::from_source("Square.jack", include_str!("vm/Square.jack")))
+        .parse_class()
+        .unwrap();
+    let mut compiler = CompilationEngine::from_writer("Square.vm", Vec::new());
+    compiler.compile_class(&class).unwrap();
+    assert_eq!(String::from_utf8(compiler.into_inner()).unwrap(), include_str!("vm/Square.vm"));
+}
+
+#[test]
+fn errors_inside_blocks_are_reported() {
+    let source = "
+        class Main {
+            function void main() {
+                if (y) { let z = 1; } else { let w = 2; }
+                while (v) { do Output.printInt(u); }
+                let a[b] = c;
+                return;
+            }
+        }";
+    let class = Parser::new(JackAnalyzer::from_source("Main.jack", source)).parse_class().unwrap();
+    let mut compiler = CompilationEngine::from_writer("Main.vm", Vec::new());
+    let errors = compiler.compile_class(&class).unwrap_err();
+    let messages: Vec<&str> = errors.iter()
+        .inspect(|error| assert_eq!(error.code, E_UNDEFINED_VARIABLE))
+        .map(|error| &*error.message)
+        .collect();
+    assert_eq!(messages, vec!["Undefined variable y", "Undefined variable z", "Undefined variable w",
+                              "Undefined variable v", "Undefined variable u", "Undefined variable a",
+                              "Undefined variable b", "Undefined variable c"]);
+}
@@ -0,0 +1,52 @@
+// Tests that the XML output matches the files of nand2tetris project 10
+
+extern crate jackcompiler;
+
+use jackcompiler::jack_analyzer::{JackAnalyzer, LexOptions};
+use jackcompiler::parser::Parser;
+use jackcompiler::vfs::MemorySink;
+use jackcompiler::xml_output;
+
+use std::path::Path;
+
+const MAIN: &str = include_str!("xml/Main.jack");
+
+fn analyzer(source: &str, string_escapes: bool) -> JackAnalyzer {
+    JackAnalyzer::from_source_with("Main.jack", source, LexOptions { string_escapes })
+}
+
+fn tokens(source: &str, string_escapes: bool) -> String {
+    let sink = MemorySink::new();
+    xml_output::write_tokens(analyzer(source, string_escapes), &mut sink.clone(), Path::new("MainT.xml")).unwrap();
+    String::from_utf8(sink.get(Path::new("MainT.xml")).unwrap()).unwrap()
+}
+
+fn parse_tree(source: &str, string_escapes: bool, annotated: bool) -> String {
+    let class = Parser::new(analyzer(source, string_escapes)).parse_class().unwrap();
+    let sink = MemorySink::new();
+    xml_output::write_parse_tree(&class, &mut sink.clone(), Path::new("Main.xml"), annotated).unwrap();
+    String::from_utf8(sink.get(Path::new("Main.xml")).unwrap()).unwrap()
+}
+
+#[test]
+fn tokens_xml() {
+    assert_eq!(tokens(MAIN, false), include_str!("xml/MainT.xml"));
+}
+
+#[test]
+fn parse_tree_xml() {
+    assert_eq!(parse_tree(MAIN, false, false), include_str!("xml/Main.xml"));
+}
+
+#[test]
+fn annotated_parse_tree_xml() {
+    assert_eq!(parse_tree(MAIN, false, true), include_str!("xml/MainAnnotated.xml"));
+}
+
+#[test]
+fn escapes_stay_on_one_line() {
+    let source = "class Main { function void main() { do Output.printString(\"\\\"a\\\" &\\n\"); return; } }";
+    let line = "<stringConstant> &quot;a&quot; &amp;\\n </stringConstant>";
+    assert!(tokens(source, true).lines().any(|tag| tag == line));
+    assert!(parse_tree(source, true, false).lines().any(|tag| tag.trim_start() == line));
+}